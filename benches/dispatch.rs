@@ -0,0 +1,70 @@
+//! Compares dispatch with and without `.indexed()` on a large protocol-like machine.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use fluent_state_machine::{EventKind, StateMachine, StateMachineBuilder};
+
+const STATES: u16 = 200;
+const EVENTS: u32 = 200_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State(u16);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Event {
+    Next,
+    Back,
+    Ping,
+    Data(u8),
+    Reset,
+}
+
+impl EventKind for Event {}
+
+// A ring of states, each with a handful of transitions, like a step in a protocol.
+fn create_protocol(indexed: bool) -> StateMachine<Event, State, u64> {
+    let builder = StateMachineBuilder::new(0, State(0));
+    let builder = if indexed { builder.indexed() } else { builder };
+
+    // Each round declares the transitions of one state and selects the next.
+    let mut builder = builder.state(State(0));
+    for state in 0..STATES {
+        let next = State((state + 1) % STATES);
+        builder = builder
+            .on(Event::Ping).update(|pings| *pings += 1)
+            .on_kind(Event::Data(0)).only_if_with_event(|_, event| *event != Event::Data(0))
+            .on(Event::Back).go_to(State((state + STATES - 1) % STATES))
+            .on(Event::Reset).go_to(State(0))
+            .on(Event::Next).go_to(next)
+            .state(next);
+    }
+    builder.build()
+}
+
+fn run(indexed: bool) -> Duration {
+    let mut protocol = create_protocol(indexed);
+    let script = [Event::Ping, Event::Data(1), Event::Next, Event::Next, Event::Back];
+
+    let start = Instant::now();
+    for event in script.iter().cycle().take(EVENTS as usize) {
+        black_box(protocol.trigger(event.clone()));
+    }
+    let elapsed = start.elapsed();
+
+    black_box(protocol.store);
+    elapsed
+}
+
+fn main() {
+    let scanned = run(false);
+    let indexed = run(true);
+
+    let per_event = |total: Duration| total / EVENTS;
+    println!("{STATES} states, {EVENTS} events");
+    println!("linear scan: {:?} per event", per_event(scanned));
+    println!("indexed:     {:?} per event", per_event(indexed));
+    println!("speedup:     {:.1}x", scanned.as_secs_f64() / indexed.as_secs_f64());
+}
@@ -0,0 +1,198 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{Error, Ident, Result};
+
+use crate::parse::{Machine, Output, StateBlock, TransitionDecl, TypeChoice};
+
+/// Checks the definition with the same rules as `StateMachineBuilder::try_build`, reporting each
+/// mistake at the identifier that causes it.
+pub fn validate(machine: &Machine) -> Result<()> {
+    let mut errors: Vec<Error> = Vec::new();
+    let declared = |state: &Ident| machine.states.iter().any(|block| block.state == *state);
+
+    for (index, block) in machine.states.iter().enumerate() {
+        if machine.states[..index].iter().any(|earlier| earlier.state == block.state) {
+            errors.push(Error::new(block.state.span(), format!("state {} is declared twice", block.state)));
+        }
+    }
+
+    if !declared(&machine.initial) {
+        errors.push(Error::new(
+            machine.initial.span(),
+            format!("initial state {} is never declared", machine.initial),
+        ));
+    }
+
+    for block in &machine.states {
+        for (index, transition) in block.transitions.iter().enumerate() {
+            let earlier = &block.transitions[..index];
+            let shadowed = earlier.iter().any(|earlier| earlier.event == transition.event && earlier.guard.is_none());
+            if matches!(machine.output, Output::Typestate) && earlier.iter().any(|earlier| earlier.event == transition.event) {
+                errors.push(Error::new(
+                    transition.event.span(),
+                    format!(
+                        "{} already has a transition on {}, a typestate machine allows one per event",
+                        block.state, transition.event
+                    ),
+                ));
+            } else if shadowed {
+                let (state, event) = (&block.state, &transition.event);
+                errors.push(Error::new(
+                    event.span(),
+                    if transition.guard.is_none() {
+                        format!("duplicate unguarded transition from {state} on {event}")
+                    } else {
+                        format!("transition from {state} on {event} is shadowed by an earlier unguarded transition")
+                    },
+                ));
+            }
+
+            if let Some(target) = transition.target.as_ref().filter(|target| !declared(target)) {
+                errors.push(Error::new(
+                    target.span(),
+                    format!("transition from {} targets undeclared state {target}", block.state),
+                ));
+            }
+        }
+    }
+
+    if declared(&machine.initial) {
+        let reachable = reachable(machine);
+        for block in machine.states.iter().filter(|block| !reachable.contains(&&block.state)) {
+            errors.push(Error::new(
+                block.state.span(),
+                format!("state {} is unreachable from the initial state", block.state),
+            ));
+        }
+    }
+
+    errors.into_iter().reduce(|mut combined, error| {
+        combined.combine(error);
+        combined
+    }).map_or(Ok(()), Err)
+}
+
+fn reachable(machine: &Machine) -> Vec<&Ident> {
+    let mut reachable = vec![&machine.initial];
+    let mut next = 0;
+    while let Some(&state) = reachable.get(next) {
+        let targets = machine
+            .states
+            .iter()
+            .filter(|block| block.state == *state)
+            .flat_map(|block| &block.transitions)
+            .filter_map(|transition| transition.target.as_ref());
+        for target in targets {
+            if !reachable.contains(&target) {
+                reachable.push(target);
+            }
+        }
+        next += 1;
+    }
+    reachable
+}
+
+/// Generates the function building a `StateMachine`, and the enums it was asked for.
+pub fn expand(machine: &Machine, state_choice: &TypeChoice, event_choice: &TypeChoice) -> TokenStream {
+    let Machine { attrs, vis, name, initial, .. } = machine;
+    let state_type = type_path(state_choice);
+    let event_type = type_path(event_choice);
+
+    let (binding, store_type, store_init) = machine.store.as_ref().map_or_else(
+        || (quote!(_), quote!(()), quote!(())),
+        |store| (store.binding.to_token_stream(), store.ty.to_token_stream(), store.init.to_token_stream()),
+    );
+
+    let states = machine.states.iter().map(|block| {
+        let state = &block.state;
+        let transitions = block
+            .transitions
+            .iter()
+            .map(|transition| expand_transition(transition, &state_type, &event_type, &binding, &store_type));
+        quote! {
+            .state(#state_type::#state)
+                #(#transitions)*
+        }
+    });
+
+    let state_enum = generated_enum(state_choice, vis, machine.states.iter().map(|block| &block.state));
+    let event_enum = generated_enum(event_choice, vis, events(&machine.states));
+
+    quote! {
+        #state_enum
+        #event_enum
+
+        #(#attrs)*
+        #vis fn #name() -> ::fluent_state_machine::StateMachine<#event_type, #state_type, #store_type> {
+            ::fluent_state_machine::StateMachineBuilder::new(#store_init, #state_type::#initial)
+                #(#states)*
+                .build()
+        }
+    }
+}
+
+fn expand_transition(
+    transition: &TransitionDecl,
+    state_type: &TokenStream,
+    event_type: &TokenStream,
+    binding: &TokenStream,
+    store_type: &TokenStream,
+) -> TokenStream {
+    let event = &transition.event;
+    let mut tokens = quote!(.on(#event_type::#event));
+
+    if let Some(target) = &transition.target {
+        tokens.extend(quote!(.go_to(#state_type::#target)));
+    }
+    if let Some(guard) = &transition.guard {
+        tokens.extend(quote_spanned! {event.span()=>
+            .only_if(|#[allow(unused_variables)] #binding: &#store_type| #guard)
+        });
+    }
+    if let Some(action) = &transition.action {
+        tokens.extend(quote_spanned! {event.span()=>
+            .update(|#[allow(unused_variables)] #binding: &mut #store_type| { #action; })
+        });
+        // `do` only runs once the guard has passed.
+        if transition.guard.is_some() {
+            tokens.extend(quote!(.guard_first()));
+        }
+    }
+    tokens
+}
+
+fn type_path(choice: &TypeChoice) -> TokenStream {
+    match choice {
+        TypeChoice::Generate(name) => name.to_token_stream(),
+        TypeChoice::Existing(path) => path.to_token_stream(),
+    }
+}
+
+/// Declares a unit-only enum with the given variants when the user asked for `enum Name`.
+fn generated_enum<'a>(
+    choice: &TypeChoice,
+    vis: &syn::Visibility,
+    variants: impl Iterator<Item = &'a Ident>,
+) -> TokenStream {
+    let TypeChoice::Generate(name) = choice else {
+        return TokenStream::new();
+    };
+    let variants = variants.collect::<Vec<_>>();
+    quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #vis enum #name {
+            #(#variants,)*
+        }
+    }
+}
+
+/// Every event used by a transition, each listed once, in the order they first appear.
+fn events(states: &[StateBlock]) -> impl Iterator<Item = &Ident> {
+    let mut events: Vec<&Ident> = Vec::new();
+    for transition in states.iter().flat_map(|block| &block.transitions) {
+        if !events.contains(&&transition.event) {
+            events.push(&transition.event);
+        }
+    }
+    events.into_iter()
+}
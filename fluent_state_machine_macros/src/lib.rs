@@ -0,0 +1,216 @@
+//! The `state_machine!` macro, a declarative front-end for `fluent_state_machine`.
+//!
+//! Use it through the `macros` feature of `fluent_state_machine`.
+
+mod expand;
+mod parse;
+mod typestate;
+
+use proc_macro::TokenStream;
+
+/// Declares a function that builds a `StateMachine`, checking the definition at compile time.
+///
+/// ```ignore
+/// state_machine! {
+///     /// A CD player that counts the selected track.
+///     pub fn create_cd_player;
+///     state enum State;
+///     event enum Event;
+///     store track: u32 = 0;
+///     initial Stopped;
+///
+///     Stopped {
+///         Play => Playing if *track > 0,
+///         Forward do *track += 1,
+///     }
+///     Playing {
+///         Pause => Paused,
+///         Stop => Stopped do *track = 0,
+///     }
+///     Paused {
+///         Play => Playing,
+///         Stop => Stopped do *track = 0,
+///     }
+/// }
+/// ```
+///
+/// - `state enum State;` and `event enum Event;` generate unit enums with every state and
+///   event used below. Leave out `enum` to use existing enums, for example `state crate::State;`.
+/// - `store` names the store and gives its type and initial value. Guards see it by reference
+///   and `do` actions by mutable reference. Without `store`, the store is `()`.
+/// - `Event => Target` is an external transition, and `Event` alone an internal one.
+///   `if` adds a guard and `do` an action that runs once the guard has passed.
+///
+/// Duplicate states, duplicate or shadowed transitions, undeclared targets and unreachable
+/// states are compile errors pointing at the offending name.
+///
+/// # Typestate machines
+///
+/// Starting with `mod name;` instead of `fn name;` generates a module with a struct per state
+/// instead of a `StateMachine`, so invalid transitions do not compile. Each transition becomes a
+/// method named after its event that consumes the state and returns the next one; a guarded
+/// transition returns `Err(self)` when its guard fails. The store is the `store` field of every
+/// state, and `new()` creates the initial state. There are no `state` and `event` declarations,
+/// and each state allows one transition per event.
+///
+/// ```
+/// use fluent_state_machine_macros::state_machine;
+///
+/// state_machine! {
+///     mod cd_player;
+///     store track: u32 = 0;
+///     initial Stopped;
+///
+///     Stopped { Play => Playing if *track > 0, Forward do *track += 1 }
+///     Playing { Pause => Paused, Stop => Stopped }
+///     Paused { Play => Playing, Stop => Stopped }
+/// }
+///
+/// let stopped: cd_player::Stopped = cd_player::new().forward();
+/// let Ok(playing) = stopped.play() else { unreachable!() };
+/// let paused: cd_player::Paused = playing.pause();
+/// ```
+///
+/// Pausing a stopped player is not a transition, so it does not compile:
+///
+/// ```compile_fail
+/// use fluent_state_machine_macros::state_machine;
+///
+/// state_machine! {
+///     mod cd_player;
+///     initial Stopped;
+///
+///     Stopped { Play => Playing }
+///     Playing { Pause => Stopped }
+/// }
+///
+/// cd_player::new().pause();
+/// ```
+#[proc_macro]
+pub fn state_machine(input: TokenStream) -> TokenStream {
+    expand(input.into()).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let machine: parse::Machine = syn::parse2(input)?;
+    expand::validate(&machine)?;
+    Ok(match &machine.output {
+        parse::Output::Function { state_type, event_type } => expand::expand(&machine, state_type, event_type),
+        parse::Output::Typestate => typestate::expand(&machine)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::expand;
+
+    fn errors(input: proc_macro2::TokenStream) -> Vec<String> {
+        expand(input).err().into_iter().flatten().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn test_valid_machine() {
+        let input = quote! {
+            fn create_turnstile;
+            state enum State;
+            event enum Event;
+            initial Locked;
+
+            Locked { Coin => Unlocked }
+            Unlocked { Push => Locked }
+        };
+        assert!(expand(input).is_ok());
+    }
+
+    #[test]
+    fn test_definition_errors() {
+        let input = quote! {
+            fn create_turnstile;
+            state enum State;
+            event enum Event;
+            initial Locked;
+
+            Locked {
+                Coin => Unlocked,
+                Coin => Unlocked if true,
+                Push => Broken,
+            }
+            Unlocked {
+                Push => Locked,
+                Push => Unlocked,
+            }
+            Unlocked {}
+            Orphan {}
+        };
+        assert_eq!(
+            errors(input),
+            [
+                "state Unlocked is declared twice",
+                "transition from Locked on Coin is shadowed by an earlier unguarded transition",
+                "transition from Locked targets undeclared state Broken",
+                "duplicate unguarded transition from Unlocked on Push",
+                "state Orphan is unreachable from the initial state",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undeclared_initial_state() {
+        let input = quote! {
+            fn create_turnstile;
+            state enum State;
+            event enum Event;
+            initial Idle;
+
+            Locked {}
+        };
+        assert_eq!(errors(input), ["initial state Idle is never declared"]);
+    }
+
+    #[test]
+    fn test_typestate_allows_one_transition_per_event() {
+        let input = quote! {
+            mod soda_machine;
+            store cans: (u32, u32) = (1, 1);
+            initial Select;
+
+            Select {
+                Coke if cans.0 > 0 do cans.0 -= 1,
+                Coke if cans.1 > 0 do cans.1 -= 1,
+            }
+        };
+        assert_eq!(
+            errors(input),
+            ["Select already has a transition on Coke, a typestate machine allows one per event"]
+        );
+    }
+
+    #[test]
+    fn test_typestate_has_no_enums() {
+        let input = quote! {
+            mod turnstile;
+            state enum State;
+            initial Locked;
+
+            Locked {}
+        };
+        assert_eq!(
+            errors(input),
+            ["a typestate machine has no `state` type, its states and events come from the state blocks"]
+        );
+    }
+
+    #[test]
+    fn test_missing_header() {
+        let input = quote! {
+            fn create_turnstile;
+            state enum State;
+            initial Locked;
+
+            Locked {}
+        };
+        assert_eq!(errors(input), ["missing `event` declaration"]);
+    }
+}
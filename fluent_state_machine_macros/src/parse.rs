@@ -0,0 +1,185 @@
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Attribute, Error, Expr, Ident, Path, Result, Token, Type, Visibility};
+
+/// The whole input of `state_machine!`.
+pub struct Machine {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub name: Ident,
+    pub output: Output,
+    pub store: Option<Store>,
+    pub initial: Ident,
+    pub states: Vec<StateBlock>,
+}
+
+/// What the macro generates.
+pub enum Output {
+    /// `fn name;`: a function building a `StateMachine` over the given enums.
+    Function { state_type: TypeChoice, event_type: TypeChoice },
+    /// `mod name;`: a module with a type per state and a method per transition.
+    Typestate,
+}
+
+/// Whether the macro generates the `State` or `Event` enum or uses one the user wrote.
+pub enum TypeChoice {
+    Generate(Ident),
+    Existing(Path),
+}
+
+/// `store track: u32 = 0;`
+pub struct Store {
+    pub binding: Ident,
+    pub ty: Type,
+    pub init: Expr,
+}
+
+/// `Stopped { Play => Playing if *track > 0, ... }`
+pub struct StateBlock {
+    pub state: Ident,
+    pub transitions: Vec<TransitionDecl>,
+}
+
+/// `Event [=> Target] [if guard] [do action]`
+pub struct TransitionDecl {
+    pub event: Ident,
+    pub target: Option<Ident>,
+    pub guard: Option<Expr>,
+    pub action: Option<Expr>,
+}
+
+impl Parse for Machine {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis: Visibility = input.parse()?;
+        let typestate = if input.peek(Token![mod]) {
+            input.parse::<Token![mod]>()?;
+            true
+        } else {
+            input.parse::<Token![fn]>()?;
+            false
+        };
+        let name: Ident = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut state_type = None;
+        let mut event_type = None;
+        let mut store = None;
+        let mut initial = None;
+
+        while input.peek(Ident) && !input.peek2(syn::token::Brace) {
+            let keyword: Ident = input.parse()?;
+            match keyword.to_string().as_str() {
+                "state" | "event" if typestate => {
+                    return Err(Error::new(
+                        keyword.span(),
+                        format!("a typestate machine has no `{keyword}` type, its states and events come from the state blocks"),
+                    ))
+                }
+                "state" => set_once(&mut state_type, &keyword, input.parse()?)?,
+                "event" => set_once(&mut event_type, &keyword, input.parse()?)?,
+                "store" => set_once(&mut store, &keyword, input.parse()?)?,
+                "initial" => set_once(&mut initial, &keyword, input.parse()?)?,
+                _ => {
+                    return Err(Error::new(
+                        keyword.span(),
+                        "expected `state`, `event`, `store`, `initial` or a state block",
+                    ))
+                }
+            }
+            input.parse::<Token![;]>()?;
+        }
+
+        let mut states = Vec::new();
+        while !input.is_empty() {
+            states.push(input.parse()?);
+        }
+
+        let missing = |header: &str| Error::new(name.span(), format!("missing `{header}` declaration"));
+        let output = if typestate {
+            Output::Typestate
+        } else {
+            Output::Function {
+                state_type: state_type.ok_or_else(|| missing("state"))?,
+                event_type: event_type.ok_or_else(|| missing("event"))?,
+            }
+        };
+        Ok(Self {
+            output,
+            initial: initial.ok_or_else(|| missing("initial"))?,
+            attrs,
+            vis,
+            name,
+            store,
+            states,
+        })
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, keyword: &Ident, value: T) -> Result<()> {
+    if slot.is_some() {
+        return Err(Error::new(keyword.span(), format!("`{keyword}` is declared twice")));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+impl Parse for TypeChoice {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![enum]) {
+            input.parse::<Token![enum]>()?;
+            Ok(Self::Generate(input.parse()?))
+        } else {
+            Ok(Self::Existing(input.parse()?))
+        }
+    }
+}
+
+impl Parse for Store {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let binding = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let init = input.parse()?;
+        Ok(Self { binding, ty, init })
+    }
+}
+
+impl Parse for StateBlock {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let state = input.parse()?;
+        let content;
+        braced!(content in input);
+        let transitions = Punctuated::<TransitionDecl, Token![,]>::parse_terminated(&content)?;
+        Ok(Self {
+            state,
+            transitions: transitions.into_iter().collect(),
+        })
+    }
+}
+
+impl Parse for TransitionDecl {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let event = input.parse()?;
+        let target = if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let guard = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let action = if input.peek(Token![do]) {
+            input.parse::<Token![do]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(Self { event, target, guard, action })
+    }
+}
@@ -0,0 +1,167 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{Error, Ident, Result};
+
+use crate::parse::{Machine, StateBlock, TransitionDecl};
+
+/// Generates a module with a struct per state and, on each, a method per transition that
+/// consumes the state and returns the next one.
+pub fn expand(machine: &Machine) -> Result<TokenStream> {
+    let Machine { attrs, vis, name, initial, .. } = machine;
+
+    let structs = machine.states.iter().map(|block| {
+        let state = &block.state;
+        machine.store.as_ref().map_or_else(
+            || quote!(pub struct #state;),
+            |store| {
+                let ty = &store.ty;
+                quote!(pub struct #state { pub store: #ty })
+            },
+        )
+    });
+    let impls = machine.states.iter().map(|block| expand_state(machine, block)).collect::<Result<Vec<_>>>()?;
+
+    let new = machine.store.as_ref().map_or_else(
+        || quote!(#initial),
+        |store| {
+            let init = &store.init;
+            quote!(#initial { store: #init })
+        },
+    );
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis mod #name {
+            #[allow(unused_imports, clippy::wildcard_imports)]
+            use super::*;
+
+            #(#structs)*
+            #(#impls)*
+
+            /// Creates the machine in its initial state.
+            #[must_use]
+            pub fn new() -> #initial {
+                #new
+            }
+        }
+    })
+}
+
+fn expand_state(machine: &Machine, block: &StateBlock) -> Result<TokenStream> {
+    let state = &block.state;
+    let methods = block
+        .transitions
+        .iter()
+        .map(|transition| expand_transition(machine, transition))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! {
+        impl #state {
+            #(#methods)*
+        }
+    })
+}
+
+fn expand_transition(machine: &Machine, transition: &TransitionDecl) -> Result<TokenStream> {
+    let method = method_name(&transition.event)?;
+    let target = transition.target.as_ref().map_or_else(|| quote!(Self), |target| quote!(#target));
+
+    let action = transition.action.as_ref().map(|action| {
+        machine.store.as_ref().map_or_else(
+            || quote!({ #action; }),
+            |store| {
+                let binding = &store.binding;
+                quote!({ let #binding = &mut store; #action; })
+            },
+        )
+    });
+    let next = if machine.store.is_some() {
+        quote! {{
+            #[allow(unused_mut)]
+            let mut store = self.store;
+            #action
+            #target { store }
+        }}
+    } else {
+        quote! {{
+            #action
+            #target
+        }}
+    };
+
+    let event = &transition.event;
+    let Some(guard) = &transition.guard else {
+        return Ok(quote_spanned! {event.span()=>
+            #[must_use]
+            pub fn #method(self) -> #target #next
+        });
+    };
+
+    let check = machine.store.as_ref().map(|store| {
+        let binding = &store.binding;
+        quote!(let #binding = &self.store;)
+    });
+    Ok(quote_spanned! {event.span()=>
+        /// Fires the transition if its guard passes, otherwise hands the state back.
+        #[allow(clippy::missing_errors_doc)]
+        pub fn #method(self) -> ::core::result::Result<#target, Self> {
+            let passes = { #check #guard };
+            if passes { Ok(#next) } else { Err(self) }
+        }
+    })
+}
+
+/// `SelectTrack` becomes `select_track`, with a run of capitals kept as one word so that
+/// `HTTPRequest` becomes `http_request`. Keywords such as `Move` become raw identifiers, except
+/// for those that cannot be raw, like `Super`, which are an error.
+fn method_name(event: &Ident) -> Result<Ident> {
+    let characters: Vec<char> = event.to_string().chars().collect();
+    let mut name = String::new();
+    for (index, &character) in characters.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|previous| characters[previous]);
+        let next = characters.get(index + 1);
+        let starts_word = character.is_uppercase()
+            && previous.is_some_and(|previous| {
+                !previous.is_uppercase() && previous != '_'
+                    || previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase())
+            });
+        if starts_word {
+            name.push('_');
+        }
+        name.extend(character.to_lowercase());
+    }
+    if syn::parse_str::<Ident>(&name).is_ok() {
+        Ok(Ident::new(&name, event.span()))
+    } else if matches!(name.as_str(), "self" | "super" | "crate") {
+        Err(Error::new(event.span(), format!("event {event} cannot be a method name, as `{name}` is a reserved path keyword")))
+    } else {
+        Ok(Ident::new_raw(&name, event.span()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+
+    use super::*;
+
+    fn method(event: &str) -> String {
+        method_name(&Ident::new(event, Span::call_site())).map_or_else(|error| error.to_string(), |method| method.to_string())
+    }
+
+    #[test]
+    fn test_method_names() {
+        assert_eq!(method("SelectTrack"), "select_track");
+        assert_eq!(method("HTTPRequest"), "http_request");
+        assert_eq!(method("GetURL"), "get_url");
+        assert_eq!(method("Play2Tracks"), "play2_tracks");
+        assert_eq!(method("Move"), "r#move");
+        assert_eq!(method("SelfValue"), "self_value");
+    }
+
+    #[test]
+    fn test_path_keywords_are_errors() {
+        assert_eq!(method("Super"), "event Super cannot be a method name, as `super` is a reserved path keyword");
+        assert_eq!(method("Crate"), "event Crate cannot be a method name, as `crate` is a reserved path keyword");
+        assert_eq!(method("Self"), "event Self cannot be a method name, as `self` is a reserved path keyword");
+    }
+}
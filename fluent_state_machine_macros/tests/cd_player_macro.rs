@@ -0,0 +1,85 @@
+use fluent_state_machine::TriggerOutcome;
+use fluent_state_machine_macros::state_machine;
+
+state_machine! {
+    /// A CD player that counts the selected track.
+    fn create_cd_player;
+    state enum State;
+    event enum Event;
+    store track: u32 = 0;
+    initial Stopped;
+
+    Stopped {
+        Play => Playing if *track > 0,
+        Forward do *track += 1,
+        Backward if *track > 0 do *track -= 1,
+    }
+    Playing {
+        Pause => Paused,
+        Stop => Stopped do *track = 0,
+    }
+    Paused {
+        Play => Playing,
+        Stop => Stopped do *track = 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Light {
+    Off,
+    On,
+}
+
+#[derive(Debug, PartialEq)]
+enum Switch {
+    Toggle,
+}
+
+state_machine! {
+    fn create_light;
+    state Light;
+    event Switch;
+    initial Off;
+
+    Off { Toggle => On }
+    On { Toggle => Off }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_enums() {
+        let mut cd_player = create_cd_player();
+        assert_eq!(cd_player.state, State::Stopped);
+
+        assert_eq!(cd_player.trigger(Event::Play), TriggerOutcome::Rejected { state: State::Stopped, guards: 1 });
+        cd_player.trigger(Event::Forward);
+        cd_player.trigger(Event::Forward);
+        assert_eq!(cd_player.store, 2);
+
+        cd_player.trigger(Event::Play);
+        cd_player.trigger(Event::Pause);
+        assert_eq!(cd_player.state, State::Paused);
+
+        cd_player.trigger(Event::Stop);
+        assert_eq!(cd_player.state, State::Stopped);
+        assert_eq!(cd_player.store, 0);
+    }
+
+    #[test]
+    fn test_action_runs_after_guard() {
+        let mut cd_player = create_cd_player();
+
+        assert_eq!(cd_player.trigger(Event::Backward), TriggerOutcome::Rejected { state: State::Stopped, guards: 1 });
+        assert_eq!(cd_player.store, 0);
+    }
+
+    #[test]
+    fn test_existing_enums() {
+        let mut light = create_light();
+        assert_eq!(light.trigger(Switch::Toggle), TriggerOutcome::Transitioned { from: Light::Off, to: Light::On });
+        assert_eq!(light.store, ());
+    }
+}
@@ -0,0 +1,62 @@
+use fluent_state_machine_macros::state_machine;
+
+state_machine! {
+    /// A CD player whose state is part of its type.
+    mod cd_player;
+    store track: u32 = 0;
+    initial Stopped;
+
+    Stopped {
+        Play => Playing if *track > 0,
+        Forward do *track += 1,
+    }
+    Playing {
+        Pause => Paused,
+        Stop => Stopped do *track = 0,
+    }
+    Paused {
+        Play => Playing,
+        Stop => Stopped do *track = 0,
+    }
+}
+
+state_machine! {
+    mod turnstile;
+    initial Locked;
+
+    Locked { Coin => Unlocked, Move }
+    Unlocked { Push => Locked }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions_change_type() {
+        let stopped = cd_player::new().forward().forward();
+        assert_eq!(stopped.store, 2);
+
+        let Ok(playing) = stopped.play() else {
+            panic!("a track is selected");
+        };
+        let paused: cd_player::Paused = playing.pause();
+        let stopped: cd_player::Stopped = paused.stop();
+        assert_eq!(stopped.store, 0);
+    }
+
+    #[test]
+    fn test_rejected_guard_returns_state() {
+        let Err(stopped) = cd_player::new().play() else {
+            panic!("no track is selected");
+        };
+        assert_eq!(stopped.forward().store, 1);
+    }
+
+    #[test]
+    fn test_without_store() {
+        let locked: turnstile::Locked = turnstile::new().r#move();
+        let unlocked: turnstile::Unlocked = locked.coin();
+        let _: turnstile::Locked = unlocked.push();
+    }
+}
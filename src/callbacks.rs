@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+use std::sync::{Mutex, PoisonError};
+
+use crate::index::Index;
+use crate::{Clock, Context, Initial, StateMachineBuilder, SystemClock};
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Local {}
+    impl Sealed for super::ThreadSafe {}
+}
+
+/// How a [`MachineDefinition`](crate::MachineDefinition) boxes its actions, guards and clock,
+/// chosen when the builder is created.
+///
+/// [`Local`], the default, takes closures that hold an `Rc` or other values that cannot leave
+/// their thread. [`ThreadSafe`] takes `Send + Sync` closures, so that one definition can drive
+/// instances on many threads. This trait is sealed; these are its only implementations.
+pub trait Callbacks<Event, State, Store>: sealed::Sealed {
+    type Action: ?Sized + Fn(&mut Context<'_, Event, Store>);
+    type Guard: ?Sized + Fn(&Store, Option<&Event>) -> bool;
+    type GlobalAction: ?Sized + Fn(&mut Store, &State, &Event);
+    type Clock: ?Sized + Clock;
+    #[doc(hidden)]
+    type Index: ?Sized + Index<Event, State>;
+}
+
+/// Callbacks for a machine run on one thread, built with [`StateMachineBuilder::new`].
+///
+/// An action must not trigger another machine sharing its definition from inside itself, as
+/// that would run the same action while it is still running, which panics.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Local;
+
+impl<Event, State, Store> Callbacks<Event, State, Store> for Local {
+    type Action = dyn Fn(&mut Context<'_, Event, Store>);
+    type Guard = dyn Fn(&Store, Option<&Event>) -> bool;
+    type GlobalAction = dyn Fn(&mut Store, &State, &Event);
+    type Clock = dyn Clock;
+    type Index = dyn Index<Event, State>;
+}
+
+/// Callbacks for a definition shared across threads, built with
+/// [`StateMachineBuilder::new_thread_safe`].
+///
+/// Actions, guards and the clock must be `Send + Sync`. Instances on several threads run an
+/// action one at a time, so an action must not trigger another machine sharing its definition
+/// from inside itself, as that would wait for itself forever.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSafe;
+
+impl<Event, State, Store> Callbacks<Event, State, Store> for ThreadSafe {
+    type Action = dyn Fn(&mut Context<'_, Event, Store>) + Send + Sync;
+    type Guard = dyn Fn(&Store, Option<&Event>) -> bool + Send + Sync;
+    type GlobalAction = dyn Fn(&mut Store, &State, &Event) + Send + Sync;
+    type Clock = dyn Clock + Send + Sync;
+    type Index = dyn Index<Event, State> + Send + Sync;
+}
+
+/// A closure or clock that a definition with these [`Callbacks`] can hold: any `'static` value
+/// for [`Local`], and any `'static` value that is `Send + Sync` for [`ThreadSafe`].
+///
+/// It is implemented for every such value, and lets each builder method box what it is given
+/// the way its definition needs.
+pub trait Capture<CallbackType>: Sized + 'static {
+    /// Boxes `self` as an action that `call` runs with a [`Context`].
+    #[doc(hidden)]
+    fn into_action<Event, State, Store>(
+        self,
+        call: impl Fn(&mut Self, &mut Context<'_, Event, Store>) + Send + Sync + 'static,
+    ) -> Box<<CallbackType as Callbacks<Event, State, Store>>::Action>
+    where
+        CallbackType: Callbacks<Event, State, Store>;
+
+    /// Boxes `self` as a guard that `call` checks.
+    #[doc(hidden)]
+    fn into_guard<Event, State, Store>(
+        self,
+        call: impl Fn(&Self, &Store, Option<&Event>) -> bool + Send + Sync + 'static,
+    ) -> Box<<CallbackType as Callbacks<Event, State, Store>>::Guard>
+    where
+        CallbackType: Callbacks<Event, State, Store>;
+
+    #[doc(hidden)]
+    fn into_global_action<Event, State, Store>(self) -> Box<<CallbackType as Callbacks<Event, State, Store>>::GlobalAction>
+    where
+        Self: FnMut(&mut Store, &State, &Event),
+        CallbackType: Callbacks<Event, State, Store>;
+
+    #[doc(hidden)]
+    fn into_clock<Event, State, Store>(self) -> Box<<CallbackType as Callbacks<Event, State, Store>>::Clock>
+    where
+        Self: Clock,
+        CallbackType: Callbacks<Event, State, Store>;
+
+    #[doc(hidden)]
+    fn into_index<Event, State, Store>(self) -> Box<<CallbackType as Callbacks<Event, State, Store>>::Index>
+    where
+        Self: Index<Event, State>,
+        CallbackType: Callbacks<Event, State, Store>;
+}
+
+// `RefCell` lets the shared definition call an `FnMut`.
+impl<T: 'static> Capture<Local> for T {
+    fn into_action<Event, State, Store>(
+        self,
+        call: impl Fn(&mut Self, &mut Context<'_, Event, Store>) + Send + Sync + 'static,
+    ) -> Box<<Local as Callbacks<Event, State, Store>>::Action> {
+        let action = RefCell::new(self);
+        Box::new(move |context| call(&mut action.borrow_mut(), context))
+    }
+
+    fn into_guard<Event, State, Store>(
+        self,
+        call: impl Fn(&Self, &Store, Option<&Event>) -> bool + Send + Sync + 'static,
+    ) -> Box<<Local as Callbacks<Event, State, Store>>::Guard> {
+        Box::new(move |store, event| call(&self, store, event))
+    }
+
+    fn into_global_action<Event, State, Store>(self) -> Box<<Local as Callbacks<Event, State, Store>>::GlobalAction>
+    where
+        Self: FnMut(&mut Store, &State, &Event),
+    {
+        let action = RefCell::new(self);
+        Box::new(move |store, state, event| (*action.borrow_mut())(store, state, event))
+    }
+
+    fn into_clock<Event, State, Store>(self) -> Box<<Local as Callbacks<Event, State, Store>>::Clock>
+    where
+        Self: Clock,
+    {
+        Box::new(self)
+    }
+
+    fn into_index<Event, State, Store>(self) -> Box<<Local as Callbacks<Event, State, Store>>::Index>
+    where
+        Self: Index<Event, State>,
+    {
+        Box::new(self)
+    }
+}
+
+// `Mutex` lets instances on several threads call an `FnMut`, one at a time.
+impl<T: Send + Sync + 'static> Capture<ThreadSafe> for T {
+    fn into_action<Event, State, Store>(
+        self,
+        call: impl Fn(&mut Self, &mut Context<'_, Event, Store>) + Send + Sync + 'static,
+    ) -> Box<<ThreadSafe as Callbacks<Event, State, Store>>::Action> {
+        let action = Mutex::new(self);
+        Box::new(move |context| call(&mut action.lock().unwrap_or_else(PoisonError::into_inner), context))
+    }
+
+    fn into_guard<Event, State, Store>(
+        self,
+        call: impl Fn(&Self, &Store, Option<&Event>) -> bool + Send + Sync + 'static,
+    ) -> Box<<ThreadSafe as Callbacks<Event, State, Store>>::Guard> {
+        Box::new(move |store, event| call(&self, store, event))
+    }
+
+    fn into_global_action<Event, State, Store>(self) -> Box<<ThreadSafe as Callbacks<Event, State, Store>>::GlobalAction>
+    where
+        Self: FnMut(&mut Store, &State, &Event),
+    {
+        let action = Mutex::new(self);
+        Box::new(move |store, state, event| (*action.lock().unwrap_or_else(PoisonError::into_inner))(store, state, event))
+    }
+
+    fn into_clock<Event, State, Store>(self) -> Box<<ThreadSafe as Callbacks<Event, State, Store>>::Clock>
+    where
+        Self: Clock,
+    {
+        Box::new(self)
+    }
+
+    fn into_index<Event, State, Store>(self) -> Box<<ThreadSafe as Callbacks<Event, State, Store>>::Index>
+    where
+        Self: Index<Event, State>,
+    {
+        Box::new(self)
+    }
+}
+
+impl<Event, State, Store> StateMachineBuilder<Event, State, Store, Initial, ThreadSafe>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Like [`StateMachineBuilder::new`], but builds a definition that can be shared across
+    /// threads. Its actions, guards and clock must be `Send + Sync`.
+    pub fn new_thread_safe(data_store: Store, initial_state: State) -> Self {
+        Self::with_clock(data_store, initial_state, Box::new(SystemClock))
+    }
+}
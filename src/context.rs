@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use crate::{Callbacks, MachineDefinition, MachineInstance, TriggerOutcome};
+
+/// The maximum number of raised events processed by one call to `trigger` or `advance`,
+/// unless changed with `.max_steps()` on the builder.
+pub const DEFAULT_MAX_STEPS: usize = 1000;
+
+/// What a `_with_context` action gets to work with: the store, the triggering event and a way
+/// to raise follow-up events.
+pub struct Context<'a, Event, Store> {
+    pub store: &'a mut Store,
+    event: Option<&'a Event>,
+    raised: &'a mut VecDeque<Event>,
+}
+
+impl<'a, Event, Store> Context<'a, Event, Store> {
+    pub(crate) const fn new(store: &'a mut Store, event: Option<&'a Event>, raised: &'a mut VecDeque<Event>) -> Self {
+        Self { store, event, raised }
+    }
+
+    /// The event being processed, or `None` while a timed transition fires.
+    #[must_use]
+    pub const fn event(&self) -> Option<&'a Event> {
+        self.event
+    }
+
+    /// Queues an event that the machine processes once the current one is completely handled.
+    pub fn raise(&mut self, event: Event) {
+        self.raised.push_back(event);
+    }
+}
+
+impl<Event, State, Store> MachineInstance<Event, State, Store>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Processes raised events in FIFO order, then deferred events that are no longer deferred,
+    /// until neither is left.
+    ///
+    /// Returns `StepLimitExceeded` and drops the remaining raised events if more than
+    /// `max_steps` events were processed, which usually means actions keep raising each other
+    /// in a loop. Deferred events are kept, to be replayed by a later call.
+    pub(crate) fn run_to_completion<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+    ) -> Option<TriggerOutcome<State>> {
+        let mut steps = 0;
+        loop {
+            let replayable = if self.raised.is_empty() { Some(self.replayable(definition)?) } else { None };
+            if steps == definition.max_steps {
+                let dropped = self.raised.len();
+                self.raised.clear();
+                return Some(TriggerOutcome::StepLimitExceeded { state: self.state, dropped });
+            }
+            steps += 1;
+
+            if let Some(position) = replayable {
+                // Regions that still defer the event keep it queued in its place.
+                let event = self.deferred.remove(position);
+                let regions = self.deferred_regions.remove(position);
+                let (_, deferring) = self.process(definition, &event, &regions);
+                if !deferring.is_empty() {
+                    self.deferred.insert(position, event);
+                    self.deferred_regions.insert(position, deferring);
+                }
+            } else {
+                let event = self.raised.pop_front()?;
+                self.process_all(definition, event);
+            }
+        }
+    }
+}
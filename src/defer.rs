@@ -0,0 +1,41 @@
+use crate::{Callbacks, MachineDefinition, MachineInstance};
+
+impl<Event, State, Store> MachineInstance<Event, State, Store>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Whether the active state of `region`, or one of its ancestors, defers the event.
+    pub(crate) fn region_defers<CallbackType: Callbacks<Event, State, Store>>(
+        &self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        region: usize,
+        event: &Event,
+    ) -> bool {
+        definition
+            .ancestors(self.region_state(region))
+            .filter_map(|state| definition.node(state))
+            .any(|node| node.deferred.iter().any(|matcher| matcher.matches(event)))
+    }
+
+    /// The position of the oldest deferred event that one of the regions it waits for no longer
+    /// defers.
+    pub(crate) fn replayable<CallbackType: Callbacks<Event, State, Store>>(&self, definition: &MachineDefinition<Event, State, Store, CallbackType>) -> Option<usize> {
+        self.deferred
+            .iter()
+            .zip(&self.deferred_regions)
+            .position(|(event, regions)| regions.iter().any(|&region| !self.region_defers(definition, region, event)))
+    }
+
+    /// Events waiting to be sent again once the machine leaves the states deferring them,
+    /// oldest first.
+    pub fn deferred_events(&self) -> &[Event] {
+        &self.deferred
+    }
+
+    /// Discards every deferred event without processing it and returns them, oldest first.
+    pub fn clear_deferred(&mut self) -> Vec<Event> {
+        self.deferred_regions.clear();
+        std::mem::take(&mut self.deferred)
+    }
+}
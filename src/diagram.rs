@@ -0,0 +1,172 @@
+use std::fmt::Debug;
+
+use crate::view::{TransitionTrigger, TransitionView};
+use crate::{Callbacks, History, MachineDefinition};
+
+/// The event of a transition followed by markers for its guard, actions and history, as shown
+/// on the edges of every diagram format.
+pub fn transition_label<Event: Debug, State>(transition: &TransitionView<'_, Event, State>) -> String {
+    let mut label = trigger_label(&transition.trigger);
+    match (transition.guard_name, transition.guarded) {
+        (Some(name), _) => {
+            label.push_str(" [");
+            label.push_str(name);
+            label.push(']');
+        }
+        (None, true) => label.push_str(" [guarded]"),
+        (None, false) => {}
+    }
+    if transition.has_action {
+        label.push_str(" / action");
+    }
+    match transition.history {
+        Some(History::Shallow) => label.push_str(" (H)"),
+        Some(History::Deep) => label.push_str(" (H*)"),
+        None => {}
+    }
+    label
+}
+
+/// The event a transition or deferral reacts to. For `.on_kind()` the payload is elided.
+pub fn trigger_label<Event: Debug>(trigger: &TransitionTrigger<'_, Event>) -> String {
+    match trigger {
+        TransitionTrigger::On(event) => label(event),
+        TransitionTrigger::OnKind(event) => kind_label(event),
+        TransitionTrigger::After(timeout) => format!("after {timeout:?}"),
+    }
+}
+
+/// The variant name of an event matched with `.on_kind()`, with its payload elided.
+fn kind_label<Event: Debug>(event: &Event) -> String {
+    let text = label(event);
+    if let Some(end) = text.find('(') {
+        format!("{}(..)", &text[..end])
+    } else if let Some(end) = text.find(" {") {
+        format!("{} {{ .. }}", &text[..end])
+    } else {
+        text
+    }
+}
+
+/// The `Debug` output of a state or event, without the quotes and escapes of strings and
+/// characters, so that a `"Idle"` state is labeled `Idle` like an `Idle` variant.
+pub fn label<T: Debug>(value: T) -> String {
+    let text = format!("{value:?}");
+    let quoted = text.len() >= 2
+        && ((text.starts_with('"') && text.ends_with('"')) || (text.starts_with('\'') && text.ends_with('\'')));
+    if quoted {
+        unescape(&text[1..text.len() - 1])
+    } else {
+        text
+    }
+}
+
+/// Undoes the escapes that `Debug` adds to strings and characters.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('0') => unescaped.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                unescaped.extend(u32::from_str_radix(&code, 16).ok().and_then(char::from_u32));
+            }
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Identifiers for the states of formats whose state names must be plain identifiers.
+///
+/// A state is identified by its label reduced to letters, digits and underscores. States whose
+/// identifiers collide, like `"On Off"` and `"OnOff"`, are told apart by a `_2`, `_3`, ...
+/// suffix, and a state whose label already is its identifier always keeps it.
+pub struct Identifiers<State> {
+    identifiers: Vec<(State, String)>,
+}
+
+impl<State: Copy + PartialEq + Debug> Identifiers<State> {
+    /// Identifies every distinct state, giving suffixes in the order the states come.
+    pub fn new(states: impl IntoIterator<Item = State>) -> Self {
+        let mut unique: Vec<State> = Vec::new();
+        for state in states {
+            if !unique.contains(&state) {
+                unique.push(state);
+            }
+        }
+
+        let bases: Vec<String> = unique.iter().map(|&state| identifier(state)).collect();
+        let mut identifiers: Vec<Option<String>> = vec![None; unique.len()];
+        let mut taken: Vec<String> = Vec::new();
+
+        for (position, &state) in unique.iter().enumerate() {
+            let base = &bases[position];
+            if label(state) == *base && !taken.contains(base) {
+                identifiers[position] = Some(base.clone());
+                taken.push(base.clone());
+            }
+        }
+        for (position, identifier) in identifiers.iter_mut().enumerate() {
+            if identifier.is_some() {
+                continue;
+            }
+            let base = &bases[position];
+            let mut candidate = base.clone();
+            let mut suffix = 2;
+            while taken.contains(&candidate) {
+                candidate = format!("{base}_{suffix}");
+                suffix += 1;
+            }
+            taken.push(candidate.clone());
+            *identifier = Some(candidate);
+        }
+
+        Self {
+            identifiers: unique.into_iter().zip(identifiers.into_iter().flatten()).collect(),
+        }
+    }
+
+    /// The identifier of `state`, which must be one of the states given to [`Self::new`].
+    pub fn of(&self, state: State) -> &str {
+        self.identifiers
+            .iter()
+            .find(|(known, _)| *known == state)
+            .map_or("", |(_, identifier)| identifier.as_str())
+    }
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq + Debug,
+    Event: PartialEq,
+{
+    /// Identifiers for the declared states, the initial states and every transition endpoint.
+    pub(crate) fn identifiers(&self) -> Identifiers<State> {
+        let declared = self.states().map(|view| view.state);
+        let endpoints = self.transitions().flat_map(|transition| [transition.from, transition.to]);
+        Identifiers::new(declared.chain(self.initial_states().iter().copied()).chain(endpoints))
+    }
+}
+
+/// The label of a state reduced to letters, digits and underscores, or `state` if none are left.
+fn identifier<State: Debug>(state: State) -> String {
+    let identifier: String = label(state)
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if identifier.is_empty() {
+        "state".to_string()
+    } else {
+        identifier
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Debug;
+
+use crate::diagram::{label, transition_label};
+use crate::view::{StateView, TransitionView};
+use crate::{Callbacks, MachineDefinition};
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq + Debug,
+    Event: PartialEq + Debug,
+{
+    /// Renders the machine definition as a Graphviz DOT digraph.
+    ///
+    /// Every transition becomes an edge labeled with its event, followed by `[guarded]` if it
+    /// has an `only_if` guard (or `[name]` for `only_if_named`) and `/ action` if it has an
+    /// `update` or `then` action. Internal transitions are dashed self-loops. Composite states
+    /// are drawn as clusters around their substates, and orthogonal regions as dashed clusters.
+    /// Each region's initial state is marked by an arrow from a dot.
+    /// [`StateMachine::to_dot`](crate::StateMachine::to_dot) also fills the active states.
+    ///
+    /// States and events are labeled with their `Debug` output, without the quotes of strings.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        self.dot(&[])
+    }
+
+    /// Renders the digraph with the `active` states filled.
+    pub(crate) fn dot(&self, active: &[State]) -> String {
+        let states: Vec<_> = self.states().collect();
+        let initial_states = self.initial_states();
+        let mut lines = vec!["digraph {".to_string(), "    compound=true;".to_string()];
+
+        for (region, &initial) in initial_states.iter().enumerate() {
+            let depth = if initial_states.len() > 1 {
+                lines.push(format!("    subgraph cluster_region{region} {{"));
+                lines.push("        style=dashed;".to_string());
+                lines.push("        label=\"\";".to_string());
+                2
+            } else {
+                1
+            };
+
+            lines.push(format!("{}start{region} [shape=point];", "    ".repeat(depth)));
+            for view in states.iter().filter(|view| view.region == region && view.parent.is_none()) {
+                Self::dot_state(&mut lines, &states, active, view.state, depth);
+            }
+            if depth > 1 {
+                lines.push("    }".to_string());
+            }
+
+            let (head, lhead) = self.dot_endpoint(initial, "lhead");
+            let lhead = if lhead.is_empty() { String::new() } else { format!(" [{lhead}]") };
+            lines.push(format!("    start{region} -> {head}{lhead};"));
+        }
+
+        lines.extend(self.transitions().map(|transition| self.dot_edge(&transition)));
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    fn dot_state(
+        lines: &mut Vec<String>,
+        states: &[StateView<'_, Event, State>],
+        active: &[State],
+        state: State,
+        depth: usize,
+    ) {
+        let indent = "    ".repeat(depth);
+        let substates: Vec<State> = states
+            .iter()
+            .filter(|view| view.parent == Some(state))
+            .map(|view| view.state)
+            .collect();
+
+        if substates.is_empty() {
+            let fill = if active.contains(&state) {
+                " [style=filled, fillcolor=lightgrey]"
+            } else {
+                ""
+            };
+            lines.push(format!("{indent}{}{fill};", dot_id(state)));
+        } else {
+            lines.push(format!("{indent}subgraph {} {{", dot_cluster(state)));
+            lines.push(format!("{indent}    label={};", dot_id(state)));
+            for substate in substates {
+                Self::dot_state(lines, states, active, substate, depth + 1);
+            }
+            lines.push(format!("{indent}}}"));
+        }
+    }
+
+    /// The node an edge to or from `state` attaches to, and the attribute clipping it to the
+    /// cluster when `state` is composite.
+    fn dot_endpoint(&self, state: State, clip: &str) -> (String, String) {
+        let leaf = self.initial_leaf_of(state);
+        if leaf == state {
+            (dot_id(state), String::new())
+        } else {
+            (dot_id(leaf), format!("{clip}={}", dot_cluster(state)))
+        }
+    }
+
+    fn dot_edge(&self, transition: &TransitionView<'_, Event, State>) -> String {
+        let (tail, ltail) = self.dot_endpoint(transition.from, "ltail");
+        let (head, lhead) = self.dot_endpoint(transition.to, "lhead");
+
+        let mut attributes = vec![format!("label={:?}", transition_label(transition))];
+        attributes.extend([ltail, lhead].into_iter().filter(|clip| !clip.is_empty()));
+        if transition.internal {
+            attributes.push("style=dashed".to_string());
+        }
+        format!("    {tail} -> {head} [{}];", attributes.join(", "))
+    }
+}
+
+fn dot_id<State: Debug>(state: State) -> String {
+    format!("{:?}", label(state))
+}
+
+fn dot_cluster<State: Debug>(state: State) -> String {
+    format!("{:?}", format!("cluster_{}", label(state)))
+}
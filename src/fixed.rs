@@ -0,0 +1,338 @@
+//! State machines that need neither `std` nor an allocator, for microcontrollers.
+//!
+//! Transitions live in an array of capacity `N` inside the machine, and guards and actions are
+//! plain function pointers instead of boxed closures. Only flat machines with events compared by
+//! equality are supported; hierarchy, regions, timers and the other features of
+//! [`crate::StateMachine`] need `std`.
+//!
+//! [`StaticStateMachine`] runs against a table of [`TransitionDef`]s that can be built in a
+//! `const` or `static` item, so defining a machine costs nothing at runtime:
+//!
+//! ```
+//! use fluent_state_machine::fixed::{StaticStateMachine, TransitionDef};
+//!
+//! static TURNSTILE: &[TransitionDef<&str, &str, u32>] = &[
+//!     TransitionDef::new("Locked", "Coin").go_to("Unlocked").update(|coins| *coins += 1),
+//!     TransitionDef::new("Unlocked", "Push").go_to("Locked"),
+//! ];
+//!
+//! let mut turnstile = StaticStateMachine::new(TURNSTILE, 0, "Locked");
+//! turnstile.trigger("Coin");
+//! assert_eq!(turnstile.state, "Unlocked");
+//! ```
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::{BuilderState, Initial, OnSelected, StateSelected, TriggerOutcome};
+
+/// One transition of a fixed-capacity machine.
+pub struct TransitionDef<Event, State, Store> {
+    pub from: State,
+    pub event: Event,
+    /// The state given to `.go_to()`, or `None` for an internal transition.
+    pub to: Option<State>,
+    /// Runs before the guard, like `.update()` on the `std` builder.
+    pub update: Option<fn(&mut Store)>,
+    pub guard: Option<fn(&Store) -> bool>,
+    /// Runs once the transition fires, like `.then()` on the `std` builder.
+    pub then: Option<fn(&mut Store)>,
+}
+
+impl<Event, State, Store> TransitionDef<Event, State, Store> {
+    /// An internal transition without guard or actions.
+    pub const fn new(from: State, event: Event) -> Self {
+        Self {
+            from,
+            event,
+            to: None,
+            update: None,
+            guard: None,
+            then: None,
+        }
+    }
+}
+
+impl<Event, State: Copy, Store> TransitionDef<Event, State, Store> {
+    #[must_use]
+    pub const fn go_to(mut self, target: State) -> Self {
+        self.to = Some(target);
+        self
+    }
+
+    #[must_use]
+    pub const fn update(mut self, before_event: fn(&mut Store)) -> Self {
+        self.update = Some(before_event);
+        self
+    }
+
+    #[must_use]
+    pub const fn only_if(mut self, condition: fn(&Store) -> bool) -> Self {
+        self.guard = Some(condition);
+        self
+    }
+
+    #[must_use]
+    pub const fn then(mut self, after_event: fn(&mut Store)) -> Self {
+        self.then = Some(after_event);
+        self
+    }
+}
+
+/// The builder was given more transitions than the machine's capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError {
+    pub capacity: usize,
+    /// The number of transitions declared.
+    pub required: usize,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} transitions declared but the capacity is {}", self.required, self.capacity)
+    }
+}
+
+impl core::error::Error for CapacityError {}
+
+/// A state machine with room for `N` transitions.
+pub struct StateMachine<Event, State, Store, const N: usize> {
+    transitions: [Option<TransitionDef<Event, State, Store>>; N],
+    pub state: State,
+    pub store: Store,
+}
+
+impl<Event, State, Store, const N: usize> StateMachine<Event, State, Store, N>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Sends an event to the machine and fires the first matching transition, in declaration
+    /// order, whose guard passes.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn trigger(&mut self, event: Event) -> TriggerOutcome<State> {
+        fire(self.transitions.iter().flatten(), &mut self.state, &mut self.store, &event)
+    }
+
+    /// Every transition, in the order it was declared.
+    pub fn transitions(&self) -> impl Iterator<Item = &TransitionDef<Event, State, Store>> {
+        self.transitions.iter().flatten()
+    }
+}
+
+/// A state machine running against a borrowed table of transitions, typically a `static`.
+pub struct StaticStateMachine<'table, Event, State, Store> {
+    transitions: &'table [TransitionDef<Event, State, Store>],
+    pub state: State,
+    pub store: Store,
+}
+
+impl<'table, Event, State, Store> StaticStateMachine<'table, Event, State, Store>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    pub const fn new(
+        transitions: &'table [TransitionDef<Event, State, Store>],
+        data_store: Store,
+        initial_state: State,
+    ) -> Self {
+        Self {
+            transitions,
+            state: initial_state,
+            store: data_store,
+        }
+    }
+
+    /// Sends an event to the machine and fires the first matching transition, in table order,
+    /// whose guard passes.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn trigger(&mut self, event: Event) -> TriggerOutcome<State> {
+        fire(self.transitions.iter(), &mut self.state, &mut self.store, &event)
+    }
+
+    /// Every transition, in table order.
+    pub fn transitions(&self) -> impl Iterator<Item = &'table TransitionDef<Event, State, Store>> {
+        self.transitions.iter()
+    }
+}
+
+fn fire<'a, Event, State, Store>(
+    transitions: impl Iterator<Item = &'a TransitionDef<Event, State, Store>>,
+    state: &mut State,
+    store: &mut Store,
+    event: &Event,
+) -> TriggerOutcome<State>
+where
+    State: Copy + PartialEq + 'a,
+    Event: PartialEq + 'a,
+    Store: 'a,
+{
+    let from = *state;
+    let mut rejected_guards = 0;
+
+    for transition in transitions {
+        if transition.from != from || transition.event != *event {
+            continue;
+        }
+
+        if let Some(update) = transition.update {
+            update(store);
+        }
+        if transition.guard.is_some_and(|guard| !guard(store)) {
+            rejected_guards += 1;
+            continue;
+        }
+        if let Some(then) = transition.then {
+            then(store);
+        }
+
+        *state = transition.to.unwrap_or(from);
+        return if *state == from {
+            TriggerOutcome::SelfTransitioned { state: from }
+        } else {
+            TriggerOutcome::Transitioned { from, to: *state }
+        };
+    }
+
+    if rejected_guards > 0 {
+        TriggerOutcome::Rejected { state: from, guards: rejected_guards }
+    } else {
+        TriggerOutcome::NoTransition { state: from }
+    }
+}
+
+/// Builds a [`StateMachine`] with room for `N` transitions, with the same chain of calls as
+/// [`crate::StateMachineBuilder`].
+#[must_use]
+pub struct StateMachineBuilder<Event, State, Store, const N: usize, BuilderStateType: BuilderState> {
+    transitions: [Option<TransitionDef<Event, State, Store>>; N],
+    /// The number of transitions declared, including those that did not fit.
+    declared: usize,
+    store: Store,
+    initial_state: State,
+    current_state: State,
+    _builder_state: PhantomData<BuilderStateType>,
+}
+
+impl<Event, State, Store, const N: usize> StateMachineBuilder<Event, State, Store, N, Initial>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    pub const fn new(data_store: Store, initial_state: State) -> Self {
+        Self {
+            transitions: [const { None }; N],
+            declared: 0,
+            store: data_store,
+            initial_state,
+            current_state: initial_state,
+            _builder_state: PhantomData,
+        }
+    }
+}
+
+impl<Event, State, Store, const N: usize> StateMachineBuilder<Event, State, Store, N, StateSelected>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    pub fn on(mut self, event: Event) -> StateMachineBuilder<Event, State, Store, N, OnSelected> {
+        self.push(event);
+        self.stage()
+    }
+}
+
+impl<Event, State, Store, const N: usize> StateMachineBuilder<Event, State, Store, N, OnSelected>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    pub fn on(mut self, event: Event) -> Self {
+        self.push(event);
+        self
+    }
+
+    pub fn go_to(mut self, target: State) -> Self {
+        if let Some(transition) = self.last_transition() {
+            transition.to = Some(target);
+        }
+        self
+    }
+
+    pub fn update(mut self, before_event: fn(&mut Store)) -> Self {
+        if let Some(transition) = self.last_transition() {
+            transition.update = Some(before_event);
+        }
+        self
+    }
+
+    pub fn only_if(mut self, condition: fn(&Store) -> bool) -> Self {
+        if let Some(transition) = self.last_transition() {
+            transition.guard = Some(condition);
+        }
+        self
+    }
+
+    pub fn then(mut self, after_event: fn(&mut Store)) -> Self {
+        if let Some(transition) = self.last_transition() {
+            transition.then = Some(after_event);
+        }
+        self
+    }
+
+    /// The transition declared last, or `None` if it did not fit.
+    fn last_transition(&mut self) -> Option<&mut TransitionDef<Event, State, Store>> {
+        self.transitions.get_mut(self.declared - 1)?.as_mut()
+    }
+}
+
+impl<Event, State, Store, const N: usize, BuilderStateType> StateMachineBuilder<Event, State, Store, N, BuilderStateType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+    BuilderStateType: BuilderState,
+{
+    pub fn state(mut self, state: State) -> StateMachineBuilder<Event, State, Store, N, StateSelected> {
+        self.current_state = state;
+        self.stage()
+    }
+
+    /// Builds the state machine.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CapacityError`] if more than `N` transitions were declared.
+    pub fn build(self) -> Result<StateMachine<Event, State, Store, N>, CapacityError> {
+        if self.declared > N {
+            return Err(CapacityError {
+                capacity: N,
+                required: self.declared,
+            });
+        }
+        Ok(StateMachine {
+            transitions: self.transitions,
+            state: self.initial_state,
+            store: self.store,
+        })
+    }
+
+    /// Adds a transition from the current state, or only counts it if the array is full.
+    fn push(&mut self, event: Event) {
+        if let Some(slot) = self.transitions.get_mut(self.declared) {
+            *slot = Some(TransitionDef::new(self.current_state, event));
+        }
+        self.declared += 1;
+    }
+
+    fn stage<Stage: BuilderState>(self) -> StateMachineBuilder<Event, State, Store, N, Stage> {
+        StateMachineBuilder {
+            transitions: self.transitions,
+            declared: self.declared,
+            store: self.store,
+            initial_state: self.initial_state,
+            current_state: self.current_state,
+            _builder_state: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::Peekable;
+
+/// Positions of the declared states and transitions of a definition, so that dispatching an
+/// event only looks at the transitions that could match it instead of scanning all of them.
+pub trait Index<Event, State> {
+    fn insert_state(&mut self, state: State, position: usize);
+
+    /// Adds a transition declared with `.on()`. Transitions of every kind must be inserted in
+    /// declaration order.
+    fn insert_exact(&mut self, state: State, event: &Event, position: usize);
+
+    /// Adds a transition declared with `.on_kind()`.
+    fn insert_kind(&mut self, state: State, position: usize);
+
+    /// Adds a transition declared with `.after()`.
+    fn insert_timer(&mut self, state: State, position: usize);
+
+    /// The position of `state` among the declared states.
+    fn state(&self, state: State) -> Option<usize>;
+
+    /// The transitions leaving `state` that might match `event`, in declaration order.
+    fn transitions(&self, state: State, event: &Event) -> Candidates<'_>;
+
+    /// The timed transitions leaving `state`, in declaration order.
+    fn timers(&self, state: State) -> &[usize];
+}
+
+/// Keys transitions declared with `.on()` by their source state and event, and groups the ones
+/// declared with `.on_kind()` by source state, as a kind may match any event.
+pub struct HashIndex<Event, State> {
+    states: HashMap<State, usize>,
+    exact: HashMap<State, HashMap<Event, Vec<usize>>>,
+    kinds: HashMap<State, Vec<usize>>,
+    timers: HashMap<State, Vec<usize>>,
+}
+
+impl<Event, State> Default for HashIndex<Event, State> {
+    fn default() -> Self {
+        Self {
+            states: HashMap::new(),
+            exact: HashMap::new(),
+            kinds: HashMap::new(),
+            timers: HashMap::new(),
+        }
+    }
+}
+
+impl<Event, State> Index<Event, State> for HashIndex<Event, State>
+where
+    State: Copy + Hash + Eq,
+    Event: Clone + Hash + Eq,
+{
+    fn insert_state(&mut self, state: State, position: usize) {
+        self.states.insert(state, position);
+    }
+
+    fn insert_exact(&mut self, state: State, event: &Event, position: usize) {
+        self.exact.entry(state).or_default().entry(event.clone()).or_default().push(position);
+    }
+
+    fn insert_kind(&mut self, state: State, position: usize) {
+        self.kinds.entry(state).or_default().push(position);
+    }
+
+    fn insert_timer(&mut self, state: State, position: usize) {
+        self.timers.entry(state).or_default().push(position);
+    }
+
+    fn state(&self, state: State) -> Option<usize> {
+        self.states.get(&state).copied()
+    }
+
+    fn transitions(&self, state: State, event: &Event) -> Candidates<'_> {
+        let exact = self.exact.get(&state).and_then(|events| events.get(event)).map_or(&[][..], Vec::as_slice);
+        let kinds = self.kinds.get(&state).map_or(&[][..], Vec::as_slice);
+        Candidates { exact: exact.iter().copied().peekable(), kinds: kinds.iter().copied().peekable() }
+    }
+
+    fn timers(&self, state: State) -> &[usize] {
+        self.timers.get(&state).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The positions of the exact and kind transitions that might match an event, merged back into
+/// declaration order.
+pub struct Candidates<'a> {
+    exact: Peekable<std::iter::Copied<std::slice::Iter<'a, usize>>>,
+    kinds: Peekable<std::iter::Copied<std::slice::Iter<'a, usize>>>,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match (self.exact.peek(), self.kinds.peek()) {
+            (Some(exact), Some(kind)) if kind < exact => self.kinds.next(),
+            (Some(_), _) => self.exact.next(),
+            (None, _) => self.kinds.next(),
+        }
+    }
+}
@@ -1,56 +1,701 @@
-pub struct Transition<Event, State, Store> {
-    event: Event,
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+mod callbacks;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(feature = "std")]
+mod defer;
+#[cfg(feature = "std")]
+mod diagram;
+#[cfg(feature = "std")]
+mod dot;
+pub mod fixed;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod machine;
+#[cfg(feature = "std")]
+mod mermaid;
+#[cfg(feature = "std")]
+mod plantuml;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+mod time;
+#[cfg(feature = "std")]
+mod validate;
+#[cfg(feature = "std")]
+mod view;
+
+#[cfg(feature = "std")]
+pub use callbacks::{Callbacks, Capture, Local, ThreadSafe};
+#[cfg(feature = "std")]
+pub use context::{Context, DEFAULT_MAX_STEPS};
+#[cfg(feature = "macros")]
+pub use fluent_state_machine_macros::state_machine;
+#[cfg(feature = "std")]
+pub use machine::StateMachine;
+#[cfg(feature = "serde")]
+pub use snapshot::{RestoreError, Snapshot};
+#[cfg(feature = "std")]
+pub use time::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "std")]
+pub use validate::BuildError;
+#[cfg(feature = "std")]
+pub use view::{StateView, TransitionTrigger, TransitionView};
+
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::hash::Hash;
+#[cfg(feature = "std")]
+use index::Index as _;
+#[cfg(feature = "std")]
+use std::mem::discriminant;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+// Callbacks are boxed as chosen by the definition's `Callbacks`. They receive `None` instead of
+// an event when a timed transition fires.
+type Action<Event, State, Store, CallbackType> = Box<<CallbackType as Callbacks<Event, State, Store>>::Action>;
+#[cfg(feature = "std")]
+type Guard<Event, State, Store, CallbackType> = Box<<CallbackType as Callbacks<Event, State, Store>>::Guard>;
+#[cfg(feature = "std")]
+type GlobalAction<Event, State, Store, CallbackType> = Box<<CallbackType as Callbacks<Event, State, Store>>::GlobalAction>;
+
+/// Events that `.on_kind()` and `.defer_kind()` can group into kinds, normally the variants of
+/// an enum.
+///
+/// For an enum, an empty `impl EventKind for Event {}` makes every variant a kind of its own,
+/// whatever its payload. Other types, such as a struct with a `kind` field, must override
+/// [`EventKind::same_kind`], as the default would put all of their values in one kind. String
+/// and number events cannot implement it, so matching them by kind does not compile:
+///
+/// ```compile_fail
+/// use fluent_state_machine::StateMachineBuilder;
+///
+/// StateMachineBuilder::new((), "Idle").state("Idle").on_kind("Start");
+/// ```
+#[cfg(feature = "std")]
+pub trait EventKind: Sized {
+    /// Whether both events are of the same kind. Defaults to being the same enum variant.
+    fn same_kind(&self, other: &Self) -> bool {
+        discriminant(self) == discriminant(other)
+    }
+}
+
+#[cfg(feature = "std")]
+enum EventMatcher<Event> {
+    /// Matches events equal to the given one.
+    Exact(Event),
+    /// Matches events of the same kind as the given one, compared with [`EventKind::same_kind`].
+    Kind(Event, fn(&Event, &Event) -> bool),
+    /// Matches no event; the transition fires once the state has been active this long.
+    After(Duration),
+}
+
+#[cfg(feature = "std")]
+impl<Event: PartialEq> EventMatcher<Event> {
+    fn matches(&self, event: &Event) -> bool {
+        match self {
+            Self::Exact(expected) => expected == event,
+            Self::Kind(expected, same_kind) => same_kind(expected, event),
+            Self::After(_) => false,
+        }
+    }
+
+    /// Whether every event matched by `other` is also matched by `self`.
+    fn covers(&self, other: &Self) -> bool {
+        match (self, other.event()) {
+            (Self::Kind(kind, same_kind), Some(event)) => same_kind(kind, event),
+            (Self::Exact(expected), Some(event)) => matches!(other, Self::Exact(_)) && expected == event,
+            _ => false,
+        }
+    }
+
+    const fn event(&self) -> Option<&Event> {
+        match self {
+            Self::Exact(event) | Self::Kind(event, _) => Some(event),
+            Self::After(_) => None,
+        }
+    }
+
+    const fn timeout(&self) -> Option<Duration> {
+        match self {
+            Self::After(timeout) => Some(*timeout),
+            Self::Exact(_) | Self::Kind(..) => None,
+        }
+    }
+}
+
+/// Which substate a composite state resumes when a transition enters it through its history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum History {
+    /// Resume the direct substate that was last active, then follow its initial substates.
+    Shallow,
+    /// Resume the innermost state that was last active, at any depth.
+    Deep,
+}
+
+#[cfg(feature = "std")]
+pub struct Transition<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> {
+    event: EventMatcher<Event>,
     from_state: State,
-    to_state: State,
-    before_event: fn(&mut Store),
-    after_event: fn(&mut Store),
-    condition: fn(&Store) -> bool,
+    to_state: Option<State>,
+    history: Option<History>,
+    before_event: Option<Action<Event, State, Store, CallbackType>>,
+    after_event: Option<Action<Event, State, Store, CallbackType>>,
+    condition: Option<Guard<Event, State, Store, CallbackType>>,
+    guard_name: Option<&'static str>,
+    /// Run `before_event` only once `condition` has passed, see `.guard_first()`.
+    guard_first: bool,
 }
 
-impl<Event, State, Store> Transition<Event, State, Store> {
-    fn new(event: Event, from_state: State) -> Self 
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> Transition<Event, State, Store, CallbackType> {
+    const fn new(event: EventMatcher<Event>, from_state: State) -> Self {
+        Self {
+            event,
+            from_state,
+            to_state: None,       // Default to internal transition
+            history: None,        // Default to entering initial substates
+            before_event: None,   // Default to no-op
+            after_event: None,    // Default to no-op
+            condition: None,      // Default to always true
+            guard_name: None,     // Default to an anonymous guard
+            guard_first: false,   // Default to running `update` before the guard
+        }
+    }
+
+    fn target(&self) -> State
     where
         State: Copy,
     {
+        self.to_state.unwrap_or(self.from_state)
+    }
+}
+
+#[cfg(feature = "std")]
+struct StateNode<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> {
+    state: State,
+    region: usize,
+    parent: Option<State>,
+    initial_substate: Option<State>,
+    on_enter: Option<Action<Event, State, Store, CallbackType>>,
+    on_exit: Option<Action<Event, State, Store, CallbackType>>,
+    deferred: Vec<EventMatcher<Event>>,
+}
+
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> StateNode<Event, State, Store, CallbackType> {
+    const fn new(state: State, region: usize) -> Self {
         Self {
-            event,
-            from_state,
-            to_state: from_state, // Default to same state
-            before_event: |_| {}, // Default to no-op
-            after_event: |_| {},  // Default to no-op
-            condition: |_| true,  // Default to always true
+            state,
+            region,
+            parent: None,
+            initial_substate: None,
+            on_enter: None,
+            on_exit: None,
+            deferred: Vec::new(),
+        }
+    }
+}
+
+/// What happened when an event was passed to [`StateMachine::trigger`].
+///
+/// New outcomes may be added as the machine gains features, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TriggerOutcome<State> {
+    /// A transition fired and the machine moved to a different state.
+    Transitioned { from: State, to: State },
+    /// A transition fired but the machine stayed in the same state.
+    SelfTransitioned { state: State },
+    /// Transitions exist for the current state and event, but every `only_if` guard rejected them.
+    Rejected { state: State, guards: usize },
+    /// No transition is defined for the current state and event.
+    NoTransition { state: State },
+    /// The event is deferred by the active state of a region and was queued until the region
+    /// enters a state that does not defer it.
+    Deferred { state: State },
+    /// Actions kept raising events beyond the limit set with `.max_steps()`. The `dropped`
+    /// raised events were discarded without being processed; deferred events stay queued.
+    StepLimitExceeded { state: State, dropped: usize },
+}
+
+/// The states, transitions and actions of a machine, without any running state.
+///
+/// A definition never changes once built, so one `Arc<MachineDefinition>` can drive any number
+/// of [`MachineInstance`]s. A [`ThreadSafe`] definition is also `Send + Sync` when its states
+/// and events are, and can drive them across threads.
+#[cfg(feature = "std")]
+pub struct MachineDefinition<Event, State, Store, CallbackType: Callbacks<Event, State, Store> = Local> {
+    global_function_after_transition: Option<GlobalAction<Event, State, Store, CallbackType>>,
+    transitions: Vec<Transition<Event, State, Store, CallbackType>>,
+    states: Vec<StateNode<Event, State, Store, CallbackType>>,
+    /// The initial state of every region, in declaration order.
+    initial_states: Vec<State>,
+    clock: Box<CallbackType::Clock>,
+    max_steps: usize,
+    /// Filled by `build()` when the builder was told `.indexed()`; without it, lookups scan.
+    index: Option<Box<CallbackType::Index>>,
+    /// Tells definitions apart, so an instance can refuse one it was not created from.
+    id: usize,
+}
+
+/// The `id` of the next definition created by a builder.
+#[cfg(feature = "std")]
+static NEXT_DEFINITION_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Creates an instance in the initial states, with its own store.
+    ///
+    /// Entering the initial states does not run their entry actions.
+    pub fn instance(&self, store: Store) -> MachineInstance<Event, State, Store> {
+        let mut instance = MachineInstance {
+            region_states: self.initial_states[1..].to_vec(),
+            history: Vec::new(),
+            last_poll: self.clock.now(),
+            elapsed: Duration::ZERO,
+            entered_at: Duration::ZERO,
+            timers: Vec::new(),
+            raised: VecDeque::new(),
+            deferred: Vec::new(),
+            deferred_regions: Vec::new(),
+            definition: self.id,
+            state: self.initial_states[0],
+            store,
+        };
+        for (region, &initial_state) in self.initial_states.iter().enumerate() {
+            let leaf = self.initial_leaf_of(initial_state);
+            instance.set_region_state(region, leaf);
+
+            let active: Vec<State> = self.ancestors(leaf).collect();
+            instance.arm_timers(self, region, &active);
         }
+        instance
+    }
+
+    fn node(&self, state: State) -> Option<&StateNode<Event, State, Store, CallbackType>> {
+        self.index.as_ref().map_or_else(
+            || self.states.iter().find(|node| node.state == state),
+            |index| index.state(state).map(|position| &self.states[position]),
+        )
+    }
+
+    fn is_declared(&self, state: State) -> bool {
+        self.node(state).is_some()
+    }
+
+    fn parent_of(&self, state: State) -> Option<State> {
+        self.node(state)?.parent
+    }
+
+    /// The given state followed by its parent, grandparent and so on.
+    ///
+    /// Bounded by the number of declared states so a cyclic hierarchy built with `build()`
+    /// cannot loop forever; `try_build()` reports such cycles.
+    fn ancestors(&self, state: State) -> impl Iterator<Item = State> + '_ {
+        std::iter::successors(Some(state), |&state| self.parent_of(state)).take(self.states.len() + 1)
+    }
+
+    /// The substate entered when `state` itself is entered, if it is a composite state.
+    ///
+    /// This is the substate chosen with `.initial_substate()`, or the first declared substate.
+    fn initial_substate_of(&self, state: State) -> Option<State> {
+        self.node(state)
+            .and_then(|node| node.initial_substate)
+            .or_else(|| {
+                self.states
+                    .iter()
+                    .find(|node| node.parent == Some(state))
+                    .map(|node| node.state)
+            })
+    }
+
+    /// The chain of initial substates entered below `state`, outermost first.
+    fn initial_substates(&self, state: State) -> impl Iterator<Item = State> + '_ {
+        std::iter::successors(self.initial_substate_of(state), |&state| self.initial_substate_of(state))
+            .take(self.states.len())
+    }
+
+    /// The innermost state reached by entering `state` and following initial substates.
+    fn initial_leaf_of(&self, state: State) -> State {
+        self.initial_substates(state).last().unwrap_or(state)
+    }
+
+    /// The transitions leaving `source` that match `event`, in declaration order.
+    fn candidates<'a>(&'a self, source: State, event: &'a Event) -> impl Iterator<Item = usize> + 'a {
+        let indexed = self.index.as_ref().map(|index| index.transitions(source, event));
+        let scanned = self.index.is_none().then(|| {
+            (0..self.transitions.len()).filter(move |&index| self.transitions[index].from_state == source)
+        });
+        indexed
+            .into_iter()
+            .flatten()
+            .chain(scanned.into_iter().flatten())
+            .filter(move |&index| self.transitions[index].event.matches(event))
+    }
+
+    /// The timed transitions leaving any of `states`, in declaration order.
+    fn timers_leaving(&self, states: &[State]) -> Vec<usize> {
+        self.index.as_ref().map_or_else(
+            || {
+                (0..self.transitions.len())
+                    .filter(|&index| self.transitions[index].event.timeout().is_some())
+                    .filter(|&index| states.contains(&self.transitions[index].from_state))
+                    .collect()
+            },
+            |index| {
+                let mut timers: Vec<usize> = states.iter().flat_map(|&state| index.timers(state)).copied().collect();
+                timers.sort_unstable();
+                timers
+            },
+        )
+    }
+
+    fn guard_passes(&self, index: usize, store: &Store, event: Option<&Event>) -> bool {
+        self.transitions[index]
+            .condition
+            .as_ref()
+            .is_none_or(|condition| condition(store, event))
     }
 }
 
-pub struct StateMachine<Event, State, Store> {
-    global_function_after_transition: fn(&mut Store, &State, &Event),
-    transitions: Vec<Transition<Event, State, Store>>,
+/// The running part of a machine: its active states, its store and its pending timers and
+/// events.
+///
+/// An instance does not own its transitions. Every method that runs the machine takes the
+/// [`MachineDefinition`] it was created from with [`MachineDefinition::instance`], which keeps
+/// instances small when many of them share one definition. [`StateMachine`] bundles an instance
+/// with its definition for the common case of a single machine.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct MachineInstance<Event, State, Store> {
+    /// Active states of the orthogonal regions added with `.region()`; region 0 is `state`.
+    region_states: Vec<State>,
+    /// The innermost state that was active when each composite state was last exited.
+    history: Vec<(State, State)>,
+    last_poll: Instant,
+    /// Time advanced since the instance was created, used for the deadlines of `timers`.
+    elapsed: Duration,
+    /// The value of `elapsed` when `state` was last entered.
+    entered_at: Duration,
+    timers: Vec<time::Timer>,
+    /// Events raised by actions that are waiting to be processed.
+    raised: VecDeque<Event>,
+    /// Events declared with `.defer()` that arrived while an active state deferred them.
+    deferred: Vec<Event>,
+    /// For each of `deferred`, the regions that deferred it and have yet to process it.
+    deferred_regions: Vec<Vec<usize>>,
+    /// The `id` of the definition this instance was created from.
+    definition: usize,
     pub state: State,
     pub store: Store,
 }
 
-impl<Event, State, Store> StateMachine<Event, State, Store>
+#[cfg(feature = "std")]
+impl<Event, State, Store> MachineInstance<Event, State, Store>
 where
     State: Copy + PartialEq,
     Event: PartialEq,
 {
-    #[allow(clippy::needless_pass_by_value)]
-    pub fn trigger(&mut self, event: Event) {
-        for transition in &mut self.transitions {
-            if transition.event != event || self.state != transition.from_state {
-                continue;
+    /// The substates below `composite` to enter when resuming it through its history, outermost
+    /// first, or `None` if the composite state has never been exited.
+    fn resumed_substates<CallbackType: Callbacks<Event, State, Store>>(
+        &self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        composite: State,
+        history: History,
+    ) -> Option<Vec<State>> {
+        let &(_, last_leaf) = self.history.iter().find(|(state, _)| *state == composite)?;
+        let mut path: Vec<State> = definition.ancestors(last_leaf).take_while(|&state| state != composite).collect();
+        path.reverse();
+
+        if history == History::Shallow {
+            let substate = *path.first()?;
+            path = std::iter::once(substate).chain(definition.initial_substates(substate)).collect();
+        }
+        Some(path)
+    }
+
+    /// Panics unless `definition` is the one this instance was created from, as the positions
+    /// of its pending timers and the states it is in only mean something there.
+    fn expect_definition<CallbackType: Callbacks<Event, State, Store>>(&self, definition: &MachineDefinition<Event, State, Store, CallbackType>) {
+        assert!(
+            self.definition == definition.id,
+            "a MachineInstance must be run with the MachineDefinition that created it"
+        );
+    }
+
+    fn remember_history(&mut self, composite: State, leaf: State) {
+        match self.history.iter_mut().find(|(state, _)| *state == composite) {
+            Some(entry) => entry.1 = leaf,
+            None => self.history.push((composite, leaf)),
+        }
+    }
+
+    fn region_state(&self, region: usize) -> State {
+        if region == 0 {
+            self.state
+        } else {
+            self.region_states[region - 1]
+        }
+    }
+
+    fn set_region_state(&mut self, region: usize, state: State) {
+        if region == 0 {
+            self.state = state;
+        } else {
+            self.region_states[region - 1] = state;
+        }
+    }
+
+    /// The active state of every region, in the order the regions were declared.
+    ///
+    /// The first entry is `state`, followed by one entry per `.region()` of the builder.
+    pub fn configuration(&self) -> Vec<State> {
+        std::iter::once(self.state).chain(self.region_states.iter().copied()).collect()
+    }
+
+    /// See [`StateMachine::is_in`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn is_in<CallbackType: Callbacks<Event, State, Store>>(&self, definition: &MachineDefinition<Event, State, Store, CallbackType>, state: State) -> bool {
+        self.expect_definition(definition);
+        (0..=self.region_states.len())
+            .any(|region| definition.ancestors(self.region_state(region)).any(|active| active == state))
+    }
+
+    /// See [`StateMachine::can_trigger`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn can_trigger<CallbackType: Callbacks<Event, State, Store>>(&self, definition: &MachineDefinition<Event, State, Store, CallbackType>, event: &Event) -> bool {
+        self.expect_definition(definition);
+        (0..=self.region_states.len()).filter(|&region| !self.region_defers(definition, region, event)).any(|region| {
+            definition.ancestors(self.region_state(region)).any(|source| {
+                definition.candidates(source, event).any(|index| {
+                    // A guard that would see what its update did cannot be checked without
+                    // running the update, so the transition may fire.
+                    let transition = &definition.transitions[index];
+                    let update_first = transition.before_event.is_some() && !transition.guard_first;
+                    update_first || definition.guard_passes(index, &self.store, Some(event))
+                })
+            })
+        })
+    }
+
+    /// See [`StateMachine::permitted_events`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    #[must_use]
+    pub fn permitted_events<'a, CallbackType: Callbacks<Event, State, Store>>(&self, definition: &'a MachineDefinition<Event, State, Store, CallbackType>) -> Vec<&'a Event> {
+        definition.events().into_iter().filter(|event| self.can_trigger(definition, event)).collect()
+    }
+
+    /// See [`StateMachine::trigger`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn trigger<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, event: Event) -> TriggerOutcome<State> {
+        let outcomes = self.trigger_regions(definition, event);
+        if let Some(&limit @ TriggerOutcome::StepLimitExceeded { .. }) = outcomes.last() {
+            return limit;
+        }
+        let fired = outcomes.iter().find(|outcome| {
+            matches!(outcome, TriggerOutcome::Transitioned { .. } | TriggerOutcome::SelfTransitioned { .. })
+        });
+        let rejected = outcomes.iter().find(|outcome| matches!(outcome, TriggerOutcome::Rejected { .. }));
+        let deferred = outcomes.iter().find(|outcome| matches!(outcome, TriggerOutcome::Deferred { .. }));
+        *fired.or(rejected).or(deferred).unwrap_or(&outcomes[0])
+    }
+
+    /// See [`StateMachine::trigger_regions`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn trigger_regions<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        event: Event,
+    ) -> Vec<TriggerOutcome<State>> {
+        self.expect_definition(definition);
+        let mut outcomes = self.process_all(definition, event);
+        outcomes.extend(self.run_to_completion(definition));
+        outcomes
+    }
+
+    /// Offers the event to every region, and queues it for the regions that defer it.
+    fn process_all<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, event: Event) -> Vec<TriggerOutcome<State>> {
+        let regions: Vec<usize> = (0..=self.region_states.len()).collect();
+        let (outcomes, deferring) = self.process(definition, &event, &regions);
+        if !deferring.is_empty() {
+            self.deferred.push(event);
+            self.deferred_regions.push(deferring);
+        }
+        outcomes
+    }
+
+    /// Dispatches the event to each of `regions` whose active state does not defer it. Returns
+    /// the outcome of each region, and the regions that deferred it.
+    fn process<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        event: &Event,
+        regions: &[usize],
+    ) -> (Vec<TriggerOutcome<State>>, Vec<usize>) {
+        let mut outcomes = Vec::new();
+        let mut deferring = Vec::new();
+        for &region in regions {
+            if self.region_defers(definition, region, event) {
+                deferring.push(region);
+                outcomes.push(TriggerOutcome::Deferred { state: self.region_state(region) });
+            } else {
+                outcomes.push(self.dispatch(definition, region, event));
+            }
+        }
+        (outcomes, deferring)
+    }
+
+    fn dispatch<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        region: usize,
+        event: &Event,
+    ) -> TriggerOutcome<State> {
+        let from = self.region_state(region);
+        let mut rejected_guards = 0;
+
+        for source in definition.ancestors(from) {
+            for index in definition.candidates(source, event) {
+                let transition = &definition.transitions[index];
+                if !transition.guard_first {
+                    self.run_before_event(definition, index, Some(event));
+                }
+
+                if definition.guard_passes(index, &self.store, Some(event)) {
+                    if transition.guard_first {
+                        self.run_before_event(definition, index, Some(event));
+                    }
+                    let to = self.fire(definition, region, index, Some(event));
+                    self.run_global_action(definition, to, event);
+
+                    return if from == to {
+                        TriggerOutcome::SelfTransitioned { state: from }
+                    } else {
+                        TriggerOutcome::Transitioned { from, to }
+                    };
+                }
+
+                rejected_guards += 1;
             }
+        }
+
+        if rejected_guards > 0 {
+            TriggerOutcome::Rejected { state: from, guards: rejected_guards }
+        } else {
+            TriggerOutcome::NoTransition { state: from }
+        }
+    }
+
+    /// Runs the actions of a transition taken in `region`, moves the region to its new innermost
+    /// state and returns that state.
+    fn fire<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        region: usize,
+        index: usize,
+        event: Option<&Event>,
+    ) -> State {
+        let leaf = self.region_state(region);
+        let transition = &definition.transitions[index];
+        let source = transition.from_state;
+
+        let Some(target) = transition.to_state else {
+            self.run_after_event(definition, index, event);
+            return leaf;
+        };
+
+        // The innermost state containing both source and target that is left untouched. Being
+        // strict ancestors of both makes `.go_to()` external even between a state and its parent.
+        let domain = definition
+            .ancestors(source)
+            .skip(1)
+            .find(|&state| definition.ancestors(target).skip(1).any(|ancestor| ancestor == state));
+
+        let exited: Vec<State> = definition.ancestors(leaf).take_while(|&state| Some(state) != domain).collect();
+        for &composite in exited.iter().skip(1) {
+            self.remember_history(composite, leaf);
+        }
+
+        let mut entered: Vec<State> = definition.ancestors(target).take_while(|&state| Some(state) != domain).collect();
+        entered.reverse();
+        match transition.history.and_then(|history| self.resumed_substates(definition, target, history)) {
+            Some(resumed) => entered.extend(resumed),
+            None => entered.extend(definition.initial_substates(target)),
+        }
 
-            (transition.before_event)(&mut self.store);
+        let transitions = &definition.transitions;
+        self.timers.retain(|timer| !exited.contains(&transitions[timer.transition].from_state));
 
-            if (transition.condition)(&self.store) {
-                (transition.after_event)(&mut self.store);
-                self.state = transition.to_state;
-                (self.global_function_after_transition)(&mut self.store, &self.state, &event);
-                break;
+        for state in exited {
+            if let Some(on_exit) = definition.node(state).and_then(|node| node.on_exit.as_ref()) {
+                on_exit(&mut Context::new(&mut self.store, event, &mut self.raised));
+            }
+        }
+        self.run_after_event(definition, index, event);
+        self.arm_timers(definition, region, &entered);
+        for &state in &entered {
+            if let Some(on_enter) = definition.node(state).and_then(|node| node.on_enter.as_ref()) {
+                on_enter(&mut Context::new(&mut self.store, event, &mut self.raised));
             }
         }
+
+        let to = entered.last().copied().unwrap_or(target);
+        self.set_region_state(region, to);
+        if region == 0 {
+            self.entered_at = self.elapsed;
+        }
+        to
+    }
+
+    fn run_before_event<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, index: usize, event: Option<&Event>) {
+        if let Some(before_event) = &definition.transitions[index].before_event {
+            before_event(&mut Context::new(&mut self.store, event, &mut self.raised));
+        }
+    }
+
+    fn run_after_event<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, index: usize, event: Option<&Event>) {
+        if let Some(after_event) = &definition.transitions[index].after_event {
+            after_event(&mut Context::new(&mut self.store, event, &mut self.raised));
+        }
+    }
+
+    fn run_global_action<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, state: State, event: &Event) {
+        if let Some(global_action) = &definition.global_function_after_transition {
+            global_action(&mut self.store, &state, event);
+        }
     }
 }
 
@@ -64,107 +709,431 @@ impl BuilderState for Initial {}
 impl BuilderState for StateSelected {}
 impl BuilderState for OnSelected {}
 
+#[cfg(feature = "std")]
 #[must_use]
-pub struct StateMachineBuilder<Event, State, Store, BuilderStateType: BuilderState> {
-    state_machine: StateMachine<Event, State, Store>,
+pub struct StateMachineBuilder<Event, State, Store, BuilderStateType, CallbackType = Local>
+where
+    BuilderStateType: BuilderState,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    definition: MachineDefinition<Event, State, Store, CallbackType>,
+    store: Store,
     current_state: State,
+    /// Moved into the definition by `build()`, once every state and transition is declared.
+    index: Option<Box<CallbackType::Index>>,
     _builder_state: std::marker::PhantomData<BuilderStateType>,
 }
 
+#[cfg(feature = "std")]
 impl<Event, State, Store> StateMachineBuilder<Event, State, Store, Initial> 
 where
     State: Copy + PartialEq,
     Event: PartialEq,
 {
+    /// Starts a machine run on one thread, whose actions may mutate what they capture. Use
+    /// [`StateMachineBuilder::new_thread_safe`] to share the definition across threads.
     pub fn new(data_store: Store, initial_state: State) -> Self {
+        Self::with_clock(data_store, initial_state, Box::new(SystemClock))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType> StateMachineBuilder<Event, State, Store, Initial, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    fn with_clock(data_store: Store, initial_state: State, clock: Box<CallbackType::Clock>) -> Self {
         Self {
-            state_machine: StateMachine {
+            definition: MachineDefinition {
                 transitions: Vec::new(),
-                state: initial_state,
-                store: data_store,
-                global_function_after_transition: |_,_,_| {},
+                states: Vec::new(),
+                initial_states: vec![initial_state],
+                clock,
+                max_steps: DEFAULT_MAX_STEPS,
+                global_function_after_transition: None,
+                index: None,
+                id: NEXT_DEFINITION_ID.fetch_add(1, Ordering::Relaxed),
             },
+            store: data_store,
             current_state: initial_state,
+            index: None,
             _builder_state: std::marker::PhantomData,
         }
     }
 
+    /// Sets an action that runs after every transition fired by an event, with the new state
+    /// and the event. It does not run for timed transitions added with `.after()`.
     pub fn set_global_action(
         mut self,
-        global_action: fn(&mut Store, &State, &Event)
+        global_action: impl FnMut(&mut Store, &State, &Event) + Capture<CallbackType>
     ) -> Self {
-        self.state_machine.global_function_after_transition = global_action;
+        self.definition.global_function_after_transition = Some(global_action.into_global_action());
         self
     }
 
-    pub fn state(mut self, state: State) -> StateMachineBuilder<Event, State, Store, StateSelected> {
-        self.current_state = state;
-        StateMachineBuilder {
-            state_machine: self.state_machine,
-            current_state: state,
-            _builder_state: std::marker::PhantomData,
-        }
+    /// Sets the clock read by [`StateMachine::poll`]. Defaults to [`SystemClock`].
+    pub fn clock(mut self, clock: impl Clock + Capture<CallbackType>) -> Self {
+        self.definition.clock = clock.into_clock::<Event, State, Store>();
+        self
+    }
+
+    /// Limits how many raised events one call to `trigger` or `advance` processes before giving
+    /// up with `StepLimitExceeded`. Defaults to [`DEFAULT_MAX_STEPS`].
+    pub const fn max_steps(mut self, max_steps: usize) -> Self {
+        self.definition.max_steps = max_steps;
+        self
+    }
+
+    /// Groups the transitions by source state and event when the machine is built, so that
+    /// `trigger` only looks at the transitions that could fire instead of all of them.
+    ///
+    /// The machine behaves the same either way: alternatives for the same event are still tried
+    /// in declaration order. Transitions added with `.on_kind()` are grouped by state only, and
+    /// are checked for every event. A [`ThreadSafe`] definition also needs its states and events
+    /// to be `Send + Sync`.
+    pub fn indexed(mut self) -> Self
+    where
+        State: Hash + Eq,
+        Event: Clone + Hash + Eq,
+        index::HashIndex<Event, State>: Capture<CallbackType>,
+    {
+        self.index = Some(index::HashIndex::default().into_index::<Event, State, Store>());
+        self
     }
 }
 
-impl<Event, State, Store> StateMachineBuilder<Event, State, Store, StateSelected>
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType> StateMachineBuilder<Event, State, Store, StateSelected, CallbackType>
 where
     State: Copy + PartialEq,
     Event: PartialEq,
+    CallbackType: Callbacks<Event, State, Store>,
 {
-    pub fn on(mut self, event: Event) -> StateMachineBuilder<Event, State, Store, OnSelected> {
+    fn current_node(&mut self) -> &mut StateNode<Event, State, Store, CallbackType> {
+        let current_state = self.current_state;
+        self.definition
+            .states
+            .iter_mut()
+            .find(|node| node.state == current_state)
+            .expect("the StateSelected stage always has a declared state")
+    }
+
+    /// Nests the current state inside `parent`. Events the current state does not handle bubble
+    /// up to `parent`, and `is_in(parent)` holds while the machine is in the current state.
+    pub fn substate_of(mut self, parent: State) -> Self {
+        self.current_node().parent = Some(parent);
+        self
+    }
+
+    /// Chooses the substate entered when the current composite state is entered. Defaults to
+    /// the first declared substate.
+    pub fn initial_substate(mut self, substate: State) -> Self {
+        self.current_node().initial_substate = Some(substate);
+        self
+    }
+
+    /// Postpones `event` while this state or one of its substates is active. The event is kept
+    /// in [`MachineInstance::deferred_events`] and sent again after the machine enters a state
+    /// that does not defer it.
+    pub fn defer(mut self, event: Event) -> Self {
+        self.current_node().deferred.push(EventMatcher::Exact(event));
+        self
+    }
+
+    /// Like `.defer()`, but postpones every event of the same kind, for enums the same variant
+    /// regardless of its payload. See [`EventKind`].
+    pub fn defer_kind(mut self, event: Event) -> Self
+    where
+        Event: EventKind,
+    {
+        self.current_node().deferred.push(EventMatcher::Kind(event, Event::same_kind));
+        self
+    }
+
+    pub fn on(self, event: Event) -> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType> {
+        self.on_matching(EventMatcher::Exact(event))
+    }
+
+    /// Like `.on()`, but matches every event of the same kind, for enums the same variant
+    /// regardless of its payload, so `.on_kind(SetBrightness(0))` also fires for
+    /// `SetBrightness(80)`. See [`EventKind`].
+    pub fn on_kind(self, event: Event) -> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType>
+    where
+        Event: EventKind,
+    {
+        self.on_matching(EventMatcher::Kind(event, Event::same_kind))
+    }
+
+    /// Adds a transition that fires once the current state has been active for `timeout`, as
+    /// measured by [`StateMachine::advance`] and [`StateMachine::poll`]. Leaving the state
+    /// cancels the timer.
+    pub fn after(self, timeout: Duration) -> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType> {
+        self.on_matching(EventMatcher::After(timeout))
+    }
+
+    fn on_matching(mut self, event: EventMatcher<Event>) -> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType> {
         let transition = Transition::new(event, self.current_state);
-        self.state_machine.transitions.push(transition);
+        self.definition.transitions.push(transition);
         
         StateMachineBuilder {
-            state_machine: self.state_machine,
+            definition: self.definition,
+            store: self.store,
             current_state: self.current_state,
+            index: self.index,
             _builder_state: std::marker::PhantomData,
         }
     }
 }
 
-impl<Event, State, Store> StateMachineBuilder<Event, State, Store, OnSelected>
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType>
 where
     State: Copy + PartialEq,
     Event: PartialEq,
+    CallbackType: Callbacks<Event, State, Store>,
 {
-    pub fn state(mut self, state: State) -> StateMachineBuilder<Event, State, Store, StateSelected> {
-        self.current_state = state;
-        StateMachineBuilder {
-            state_machine: self.state_machine,
-            current_state: state,
-            _builder_state: std::marker::PhantomData,
-        }
+    pub fn on(mut self, event: Event) -> Self {
+        let transition = Transition::new(EventMatcher::Exact(event), self.current_state);
+        self.definition.transitions.push(transition);
+        self
     }
 
-    pub fn on(mut self, event: Event) -> Self {
-        let transition = Transition::new(event, self.current_state);
-        self.state_machine.transitions.push(transition);
+    /// Like `.on()`, but matches every event of the same kind. See [`EventKind`].
+    pub fn on_kind(mut self, event: Event) -> Self
+    where
+        Event: EventKind,
+    {
+        let transition = Transition::new(EventMatcher::Kind(event, Event::same_kind), self.current_state);
+        self.definition.transitions.push(transition);
         self
     }
 
+    /// Adds a transition that fires once the current state has been active for `timeout`.
+    pub fn after(mut self, timeout: Duration) -> Self {
+        let transition = Transition::new(EventMatcher::After(timeout), self.current_state);
+        self.definition.transitions.push(transition);
+        self
+    }
+
+    fn last_transition(&mut self) -> &mut Transition<Event, State, Store, CallbackType> {
+        self.definition
+            .transitions
+            .last_mut()
+            .expect("the OnSelected stage always has at least one transition")
+    }
+
     pub fn go_to(mut self, target: State) -> Self {
-        self.state_machine.transitions.last_mut().unwrap().to_state = target;
+        let transition = self.last_transition();
+        transition.to_state = Some(target);
+        transition.history = None;
+        self
+    }
+
+    /// Like `.go_to()`, but a composite target resumes the substate that was active when it was
+    /// last exited instead of its initial substate. Falls back to the initial substate the first
+    /// time the target is entered.
+    pub fn go_to_history(mut self, target: State, history: History) -> Self {
+        let transition = self.last_transition();
+        transition.to_state = Some(target);
+        transition.history = Some(history);
+        self
+    }
+
+    /// Runs this transition's `update` action only after its `only_if` guard has passed,
+    /// instead of before the guard.
+    ///
+    /// By default `update` runs first so the guard can look at what it computed, which means a
+    /// rejected transition has still changed the store, and the next candidate transition for
+    /// the same event sees those changes. With `.guard_first()` a rejected transition leaves
+    /// the store untouched.
+    pub fn guard_first(mut self) -> Self {
+        self.last_transition().guard_first = true;
+        self
+    }
+
+    fn guard(mut self, name: Option<&'static str>, condition: Box<CallbackType::Guard>) -> Self {
+        let transition = self.last_transition();
+        transition.condition = Some(condition);
+        transition.guard_name = name;
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType> StateMachineBuilder<Event, State, Store, StateSelected, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    pub fn on_enter(mut self, action: impl FnMut(&mut Store) + Capture<CallbackType>) -> Self {
+        self.current_node().on_enter = Some(action.into_action(|action, context| action(context.store)));
         self
     }
 
-    pub fn update(mut self, before_event: fn(&mut Store)) -> Self {
-        self.state_machine.transitions.last_mut().unwrap().before_event = before_event;
+    /// Like `.on_enter()`, but the action gets a [`Context`] and can raise events.
+    pub fn on_enter_with_context(mut self, action: impl FnMut(&mut Context<'_, Event, Store>) + Capture<CallbackType>) -> Self {
+        self.current_node().on_enter = Some(action.into_action(|action, context| action(context)));
         self
     }
 
-    pub fn only_if(mut self, condition: fn(&Store) -> bool) -> Self {
-        self.state_machine.transitions.last_mut().unwrap().condition = condition;
+    pub fn on_exit(mut self, action: impl FnMut(&mut Store) + Capture<CallbackType>) -> Self {
+        self.current_node().on_exit = Some(action.into_action(|action, context| action(context.store)));
         self
     }
 
-    pub fn then(mut self, after_event: fn(&mut Store)) -> Self {
-        self.state_machine.transitions.last_mut().unwrap().after_event = after_event;
+    /// Like `.on_exit()`, but the action gets a [`Context`] and can raise events.
+    pub fn on_exit_with_context(mut self, action: impl FnMut(&mut Context<'_, Event, Store>) + Capture<CallbackType>) -> Self {
+        self.current_node().on_exit = Some(action.into_action(|action, context| action(context)));
         self
     }
+}
 
-    pub fn build(self) -> StateMachine<Event, State, Store> {
-        self.state_machine
+#[cfg(feature = "std")]
+impl<Event, State, Store, CallbackType> StateMachineBuilder<Event, State, Store, OnSelected, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    /// Adds an action that runs before the `only_if` guard is checked, even if the guard then
+    /// rejects the transition. Use `.guard_first()` to run it only when the guard passes.
+    pub fn update(mut self, before_event: impl FnMut(&mut Store) + Capture<CallbackType>) -> Self {
+        self.last_transition().before_event = Some(before_event.into_action(|action, context| action(context.store)));
+        self
     }
-}
\ No newline at end of file
+
+    /// Like `.update()`, but the action also receives the triggering event and its payload.
+    /// It is skipped when a timed transition fires, as there is no event.
+    pub fn update_with_event(mut self, before_event: impl FnMut(&mut Store, &Event) + Capture<CallbackType>) -> Self {
+        self.last_transition().before_event = Some(before_event.into_action(|action, context| {
+            if let Some(event) = context.event() {
+                action(context.store, event);
+            }
+        }));
+        self
+    }
+
+    /// Like `.update()`, but the action gets a [`Context`] and can raise events.
+    pub fn update_with_context(mut self, before_event: impl FnMut(&mut Context<'_, Event, Store>) + Capture<CallbackType>) -> Self {
+        self.last_transition().before_event = Some(before_event.into_action(|action, context| action(context)));
+        self
+    }
+
+    pub fn only_if(self, condition: impl Fn(&Store) -> bool + Capture<CallbackType>) -> Self {
+        self.guard(None, condition.into_guard(|condition, store, _| condition(store)))
+    }
+
+    /// Like `.only_if()`, but gives the guard a name that diagrams show instead of `[guarded]`.
+    pub fn only_if_named(self, name: &'static str, condition: impl Fn(&Store) -> bool + Capture<CallbackType>) -> Self {
+        self.guard(Some(name), condition.into_guard(|condition, store, _| condition(store)))
+    }
+
+    /// Like `.only_if()`, but the guard also receives the triggering event and its payload.
+    /// It rejects timed transitions, as there is no event.
+    pub fn only_if_with_event(self, condition: impl Fn(&Store, &Event) -> bool + Capture<CallbackType>) -> Self {
+        self.guard(None, condition.into_guard(|condition, store, event| event.is_some_and(|event| condition(store, event))))
+    }
+
+    pub fn then(mut self, after_event: impl FnMut(&mut Store) + Capture<CallbackType>) -> Self {
+        self.last_transition().after_event = Some(after_event.into_action(|action, context| action(context.store)));
+        self
+    }
+
+    /// Like `.then()`, but the action also receives the triggering event and its payload.
+    /// It is skipped when a timed transition fires, as there is no event.
+    pub fn then_with_event(mut self, after_event: impl FnMut(&mut Store, &Event) + Capture<CallbackType>) -> Self {
+        self.last_transition().after_event = Some(after_event.into_action(|action, context| {
+            if let Some(event) = context.event() {
+                action(context.store, event);
+            }
+        }));
+        self
+    }
+
+    /// Like `.then()`, but the action gets a [`Context`] and can raise events.
+    pub fn then_with_context(mut self, after_event: impl FnMut(&mut Context<'_, Event, Store>) + Capture<CallbackType>) -> Self {
+        self.last_transition().after_event = Some(after_event.into_action(|action, context| action(context)));
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Event, State, Store, BuilderStateType, CallbackType> StateMachineBuilder<Event, State, Store, BuilderStateType, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+    BuilderStateType: BuilderState,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    pub fn state(mut self, state: State) -> StateMachineBuilder<Event, State, Store, StateSelected, CallbackType> {
+        self.current_state = state;
+        if !self.definition.is_declared(state) {
+            let region = self.definition.initial_states.len() - 1;
+            self.definition.states.push(StateNode::new(state, region));
+        }
+        StateMachineBuilder {
+            definition: self.definition,
+            store: self.store,
+            current_state: state,
+            index: self.index,
+            _builder_state: std::marker::PhantomData,
+        }
+    }
+
+    /// Starts an orthogonal region that runs alongside the ones before it, sharing the store.
+    ///
+    /// States declared with `.state()` after this call belong to the new region, which starts
+    /// in `initial_state`, which must be one of them. Every event is offered to all regions in
+    /// declaration order.
+    pub fn region(mut self, initial_state: State) -> StateMachineBuilder<Event, State, Store, Initial, CallbackType> {
+        self.definition.initial_states.push(initial_state);
+        StateMachineBuilder {
+            definition: self.definition,
+            store: self.store,
+            current_state: initial_state,
+            index: self.index,
+            _builder_state: std::marker::PhantomData,
+        }
+    }
+
+    /// Builds the state machine. Use [`StateMachine::definition`] to share its definition
+    /// with more instances.
+    pub fn build(mut self) -> StateMachine<Event, State, Store, CallbackType> {
+        if let Some(mut index) = self.index {
+            for (position, node) in self.definition.states.iter().enumerate() {
+                index.insert_state(node.state, position);
+            }
+            for (position, transition) in self.definition.transitions.iter().enumerate() {
+                match &transition.event {
+                    EventMatcher::Exact(event) => index.insert_exact(transition.from_state, event, position),
+                    EventMatcher::Kind(..) => index.insert_kind(transition.from_state, position),
+                    EventMatcher::After(_) => index.insert_timer(transition.from_state, position),
+                }
+            }
+            self.definition.index = Some(index);
+        }
+        StateMachine::from_definition(Arc::new(self.definition), self.store)
+    }
+
+    /// Builds the state machine after checking the definition for mistakes.
+    ///
+    /// # Errors
+    ///
+    /// Returns every [`BuildError`] found: duplicate or shadowed transitions, targets and an
+    /// initial state that were never declared with `.state()`, and unreachable states.
+    #[allow(clippy::type_complexity)]
+    pub fn try_build(self) -> Result<StateMachine<Event, State, Store, CallbackType>, Vec<BuildError<Event, State>>>
+    where
+        Event: Clone,
+    {
+        let errors = validate::validate(&self.definition);
+        if errors.is_empty() {
+            Ok(self.build())
+        } else {
+            Err(errors)
+        }
+    }
+}
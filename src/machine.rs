@@ -0,0 +1,218 @@
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use crate::snapshot::{RestoreError, Snapshot};
+use crate::view::{StateView, TransitionView};
+use crate::{Callbacks, Local, MachineDefinition, MachineInstance, TriggerOutcome};
+
+/// A machine instance together with the definition it runs against.
+///
+/// The active `state` and the `store` of the instance are reachable as fields through `Deref`.
+/// Cloning a machine copies its instance and shares its definition.
+pub struct StateMachine<Event, State, Store, CallbackType: Callbacks<Event, State, Store> = Local> {
+    definition: Arc<MachineDefinition<Event, State, Store, CallbackType>>,
+    instance: MachineInstance<Event, State, Store>,
+}
+
+impl<Event, State, Store, CallbackType> Clone for StateMachine<Event, State, Store, CallbackType>
+where
+    MachineInstance<Event, State, Store>: Clone,
+    CallbackType: Callbacks<Event, State, Store>,
+{
+    fn clone(&self) -> Self {
+        Self { definition: Arc::clone(&self.definition), instance: self.instance.clone() }
+    }
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> Deref for StateMachine<Event, State, Store, CallbackType> {
+    type Target = MachineInstance<Event, State, Store>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instance
+    }
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> DerefMut for StateMachine<Event, State, Store, CallbackType> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.instance
+    }
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> StateMachine<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Creates a machine in the initial states of a shared definition, with its own store.
+    pub fn from_definition(definition: Arc<MachineDefinition<Event, State, Store, CallbackType>>, store: Store) -> Self {
+        let instance = definition.instance(store);
+        Self { definition, instance }
+    }
+
+    /// The definition this machine runs against, to create more instances of it.
+    pub const fn definition(&self) -> &Arc<MachineDefinition<Event, State, Store, CallbackType>> {
+        &self.definition
+    }
+
+    /// Whether the machine is in `state`, either directly or in one of its substates, in any region.
+    pub fn is_in(&self, state: State) -> bool {
+        self.instance.is_in(&self.definition, state)
+    }
+
+    /// Whether triggering `event` now would fire a transition in any region.
+    ///
+    /// Guards are evaluated against the current store and no action runs, so neither the
+    /// machine nor anything the actions capture changes. A guarded transition whose `update`
+    /// runs before its guard cannot be checked that way and counts as able to fire; declare it
+    /// `.guard_first()` to have its guard checked. Events deferred by every region return
+    /// `false`.
+    pub fn can_trigger(&self, event: &Event) -> bool {
+        self.instance.can_trigger(&self.definition, event)
+    }
+
+    /// The events of [`Self::events`] for which [`Self::can_trigger`] holds, for example to
+    /// disable buttons that would not do anything.
+    ///
+    /// Transitions declared with `.on_kind()` are checked with the event given to `.on_kind()`.
+    #[must_use]
+    pub fn permitted_events(&self) -> Vec<&Event> {
+        self.instance.permitted_events(&self.definition)
+    }
+
+    /// Sends an event to the machine and fires the first matching transition whose guard passes.
+    ///
+    /// Transitions of the current state are tried first. If none of them fires, the event bubbles
+    /// up to the parent state declared with `.substate_of()`, then to its parent, and so on.
+    ///
+    /// A transition declared with `.go_to()` is external: the states being left run their exit
+    /// actions from the innermost outwards, then the transition's `then` action runs, then the
+    /// states being entered run their entry actions from the outermost inwards. This also holds
+    /// when `.go_to()` targets the state the machine is already in. Entering a composite state
+    /// continues into its initial substate. A transition without `.go_to()` is internal and runs
+    /// neither exit nor entry actions.
+    ///
+    /// With orthogonal regions the event is offered to every region, see [`Self::trigger_regions`].
+    /// The returned outcome is that of the first region where a transition fired, otherwise the
+    /// first region that rejected the event, otherwise the `NoTransition` of the first region.
+    ///
+    /// Events raised by actions through [`Context::raise`](crate::Context::raise) are processed
+    /// afterwards, in the order they were raised, before `trigger` returns. If they exceed the
+    /// step limit, the outcome is `StepLimitExceeded`.
+    ///
+    /// If the active state of a region defers the event with `.defer()`, the event is queued for
+    /// that region, whose outcome is `Deferred`, while the other regions handle it as usual.
+    /// Queued events are sent again to the regions that deferred them, in the order they
+    /// arrived, once those regions leave the states deferring them. The returned outcome is
+    /// `Deferred` when no region fired or rejected the event.
+    pub fn trigger(&mut self, event: Event) -> TriggerOutcome<State> {
+        self.instance.trigger(&self.definition, event)
+    }
+
+    /// Sends an event to every region in declaration order and returns the outcome of each.
+    ///
+    /// All regions share the store, so the actions of the first region run before those of
+    /// the second, and the guards of later regions see the store as earlier regions left it.
+    ///
+    /// Raised events are then processed as for [`Self::trigger`]. If they exceed the step
+    /// limit, a `StepLimitExceeded` outcome is appended after those of the regions.
+    pub fn trigger_regions(&mut self, event: Event) -> Vec<TriggerOutcome<State>> {
+        self.instance.trigger_regions(&self.definition, event)
+    }
+
+    /// Moves the machine's time forward by `duration` and fires the timed transitions that
+    /// fall due, in the order of their deadlines. Returns the outcome of each due timer.
+    ///
+    /// A state entered by a timed transition starts its own timers at that transition's
+    /// deadline, so a single large step fires the same transitions as many small ones.
+    /// Each timer fires at most once per entry of its state. If its guard rejects the
+    /// transition, the timer stays pending and its guard is checked again on every later call,
+    /// until the state is left. Events raised by a timed transition's actions are processed
+    /// right after it, at its deadline.
+    pub fn advance(&mut self, duration: Duration) -> Vec<TriggerOutcome<State>> {
+        self.instance.advance(&self.definition, duration)
+    }
+
+    /// Advances the machine by the time the clock moved since the previous poll, or since the
+    /// machine was created. See [`Self::advance`].
+    pub fn poll(&mut self) -> Vec<TriggerOutcome<State>> {
+        self.instance.poll(&self.definition)
+    }
+
+    /// Puts the machine in the states and store of a snapshot taken with
+    /// [`MachineInstance::snapshot`].
+    ///
+    /// The restored states are entered without running their entry actions. Their timers
+    /// start over, and deferred events and history are cleared.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RestoreError`] and leaves the machine unchanged if the snapshot does not fit
+    /// the machine's definition.
+    #[cfg(feature = "serde")]
+    pub fn restore(&mut self, snapshot: Snapshot<State, Store>) -> Result<(), RestoreError<State>> {
+        self.instance.restore(&self.definition, snapshot)
+    }
+
+    /// Every transition, in the order it was declared.
+    pub fn transitions(&self) -> impl Iterator<Item = TransitionView<'_, Event, State>> {
+        self.definition.transitions()
+    }
+
+    /// The transitions declared on `state` itself, not those inherited from its parent states.
+    pub fn transitions_from(&self, state: State) -> impl Iterator<Item = TransitionView<'_, Event, State>> {
+        self.definition.transitions_from(state)
+    }
+
+    /// The states with a transition leading into `state`, each listed once. Internal
+    /// transitions do not enter a state and are not counted.
+    #[must_use]
+    pub fn predecessors_of(&self, state: State) -> Vec<State> {
+        self.definition.predecessors_of(state)
+    }
+
+    /// Every state declared with `.state()`, in declaration order.
+    pub fn states(&self) -> impl Iterator<Item = StateView<'_, Event, State>> {
+        self.definition.states()
+    }
+
+    /// Every event that a transition or `.defer()` reacts to, each listed once, in the order
+    /// they first appear. Timed transitions have no event and are not included.
+    #[must_use]
+    pub fn events(&self) -> Vec<&Event> {
+        self.definition.events()
+    }
+
+    /// The state each region starts in, in the order the regions were declared.
+    #[must_use]
+    pub fn initial_states(&self) -> &[State] {
+        self.definition.initial_states()
+    }
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> StateMachine<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq + Debug,
+    Event: PartialEq + Debug,
+{
+    /// Renders the machine as a Graphviz DOT digraph, with its active states filled. See
+    /// [`MachineDefinition::to_dot`].
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        self.definition.dot(&self.configuration())
+    }
+
+    /// See [`MachineDefinition::to_mermaid`].
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        self.definition.to_mermaid()
+    }
+
+    /// See [`MachineDefinition::to_plantuml`].
+    #[must_use]
+    pub fn to_plantuml(&self) -> String {
+        self.definition.to_plantuml()
+    }
+}
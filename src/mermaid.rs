@@ -0,0 +1,73 @@
+use std::fmt::Debug;
+
+use crate::diagram::{label, transition_label, Identifiers};
+use crate::view::StateView;
+use crate::{Callbacks, MachineDefinition};
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq + Debug,
+    Event: PartialEq + Debug,
+{
+    /// Renders the machine definition as a Mermaid `stateDiagram-v2`.
+    ///
+    /// Each region's initial state is entered from `[*]`, composite states are nested blocks
+    /// with their own `[*]` arrow to the initial substate, and every transition is labeled like
+    /// in [`Self::to_dot`], with named guards shown as `[name]`.
+    ///
+    /// States are labeled with their `Debug` output, without the quotes of strings. When that is
+    /// not a valid Mermaid identifier, the state gets an identifier made of its letters, digits
+    /// and underscores, with a `_2`, `_3`, ... suffix if another state already has it.
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        let states: Vec<_> = self.states().collect();
+        let ids = self.identifiers();
+        let mut lines = vec!["stateDiagram-v2".to_string()];
+
+        for view in &states {
+            let label = label(view.state);
+            if label != ids.of(view.state) {
+                lines.push(format!("    state \"{}\" as {}", label.replace('"', "#quot;"), ids.of(view.state)));
+            }
+        }
+        for &initial in self.initial_states() {
+            lines.push(format!("    [*] --> {}", ids.of(initial)));
+        }
+        for view in states.iter().filter(|view| view.parent.is_none()) {
+            mermaid_state(&mut lines, &states, &ids, view, 1);
+        }
+        for transition in self.transitions() {
+            lines.push(format!(
+                "    {} --> {} : {}",
+                ids.of(transition.from),
+                ids.of(transition.to),
+                transition_label(&transition)
+            ));
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+fn mermaid_state<Event, State>(
+    lines: &mut Vec<String>,
+    states: &[StateView<'_, Event, State>],
+    ids: &Identifiers<State>,
+    view: &StateView<'_, Event, State>,
+    depth: usize,
+) where
+    State: Copy + PartialEq + Debug,
+{
+    let indent = "    ".repeat(depth);
+    let Some(initial) = view.initial_substate else {
+        lines.push(format!("{indent}{}", ids.of(view.state)));
+        return;
+    };
+
+    lines.push(format!("{indent}state {} {{", ids.of(view.state)));
+    lines.push(format!("{indent}    [*] --> {}", ids.of(initial)));
+    for substate in states.iter().filter(|substate| substate.parent == Some(view.state)) {
+        mermaid_state(lines, states, ids, substate, depth + 1);
+    }
+    lines.push(format!("{indent}}}"));
+}
@@ -0,0 +1,87 @@
+use std::fmt::Debug;
+
+use crate::diagram::{label, transition_label, trigger_label, Identifiers};
+use crate::view::StateView;
+use crate::{Callbacks, MachineDefinition};
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq + Debug,
+    Event: PartialEq + Debug,
+{
+    /// Renders the machine definition as a `PlantUML` state diagram.
+    ///
+    /// Composite states are nested blocks, and states list their entry and exit actions,
+    /// deferred events and internal transitions in their description. Transitions are labeled
+    /// like in [`Self::to_dot`].
+    ///
+    /// States are labeled and identified like in [`Self::to_mermaid`].
+    #[must_use]
+    pub fn to_plantuml(&self) -> String {
+        let states: Vec<_> = self.states().collect();
+        let ids = self.identifiers();
+        let mut lines = vec!["@startuml".to_string()];
+
+        for view in &states {
+            let label = label(view.state);
+            if label != ids.of(view.state) {
+                lines.push(format!("state \"{}\" as {}", label.replace('"', "'"), ids.of(view.state)));
+            }
+        }
+        for &initial in self.initial_states() {
+            lines.push(format!("[*] --> {}", ids.of(initial)));
+        }
+        for view in states.iter().filter(|view| view.parent.is_none()) {
+            plantuml_state(&mut lines, &states, &ids, view, 0);
+        }
+
+        for view in &states {
+            let id = ids.of(view.state);
+            if view.has_entry_action {
+                lines.push(format!("{id} : entry / action"));
+            }
+            if view.has_exit_action {
+                lines.push(format!("{id} : exit / action"));
+            }
+            for trigger in &view.deferred {
+                lines.push(format!("{id} : {} / defer", trigger_label(trigger)));
+            }
+        }
+
+        for transition in self.transitions() {
+            let from = ids.of(transition.from);
+            let label = transition_label(&transition);
+            if transition.internal {
+                lines.push(format!("{from} : {label}"));
+            } else {
+                lines.push(format!("{from} --> {} : {label}", ids.of(transition.to)));
+            }
+        }
+
+        lines.push("@enduml".to_string());
+        lines.join("\n") + "\n"
+    }
+}
+
+fn plantuml_state<Event, State>(
+    lines: &mut Vec<String>,
+    states: &[StateView<'_, Event, State>],
+    ids: &Identifiers<State>,
+    view: &StateView<'_, Event, State>,
+    depth: usize,
+) where
+    State: Copy + PartialEq + Debug,
+{
+    let indent = "  ".repeat(depth);
+    let Some(initial) = view.initial_substate else {
+        lines.push(format!("{indent}state {}", ids.of(view.state)));
+        return;
+    };
+
+    lines.push(format!("{indent}state {} {{", ids.of(view.state)));
+    lines.push(format!("{indent}  [*] --> {}", ids.of(initial)));
+    for substate in states.iter().filter(|substate| substate.parent == Some(view.state)) {
+        plantuml_state(lines, states, ids, substate, depth + 1);
+    }
+    lines.push(format!("{indent}}}"));
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Callbacks, MachineDefinition, MachineInstance};
+
+/// The part of a running machine worth persisting: its active states and its store.
+///
+/// Transitions and actions are code, so they are not part of a snapshot. Restore a snapshot
+/// into a machine built from the same definition with
+/// [`StateMachine::restore`](crate::StateMachine::restore).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot<State, Store> {
+    /// The active state of the first region, like [`MachineInstance::state`].
+    pub state: State,
+    /// The active states of the regions added with `.region()`, in declaration order.
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<State>,
+    pub store: Store,
+}
+
+/// Why [`StateMachine::restore`](crate::StateMachine::restore) rejected a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreError<State> {
+    /// The snapshot has a different number of regions than the machine.
+    RegionCount { expected: usize, found: usize },
+    /// The snapshot names a state that was never declared with `.state()`.
+    UndeclaredState { state: State },
+    /// The snapshot puts a state into a region it was not declared in.
+    WrongRegion { state: State, region: usize },
+}
+
+impl<State: fmt::Debug> fmt::Display for RestoreError<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RegionCount { expected, found } => {
+                write!(f, "snapshot has {found} regions but the machine has {expected}")
+            }
+            Self::UndeclaredState { state } => {
+                write!(f, "state {state:?} is not declared in the machine")
+            }
+            Self::WrongRegion { state, region } => {
+                write!(f, "state {state:?} does not belong to region {region}")
+            }
+        }
+    }
+}
+
+impl<State: fmt::Debug> std::error::Error for RestoreError<State> {}
+
+impl<Event, State, Store> MachineInstance<Event, State, Store>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Captures the active states and a copy of the store.
+    ///
+    /// Pending timers, deferred events and the history of composite states are not captured.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot<State, Store>
+    where
+        Store: Clone,
+    {
+        Snapshot {
+            state: self.state,
+            regions: self.region_states.clone(),
+            store: self.store.clone(),
+        }
+    }
+
+    /// See [`StateMachine::restore`](crate::StateMachine::restore).
+    ///
+    /// From then on the instance runs with `definition`, even if it was created from another.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RestoreError`] if the snapshot does not fit `definition`.
+    pub fn restore<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        snapshot: Snapshot<State, Store>,
+    ) -> Result<(), RestoreError<State>> {
+        let Snapshot { state, regions, store } = snapshot;
+        let states: Vec<State> = std::iter::once(state).chain(regions).collect();
+
+        if states.len() != definition.initial_states.len() {
+            return Err(RestoreError::RegionCount { expected: definition.initial_states.len(), found: states.len() });
+        }
+        for (region, &state) in states.iter().enumerate() {
+            match definition.states.iter().find(|node| node.state == state) {
+                None => return Err(RestoreError::UndeclaredState { state }),
+                Some(node) if node.region != region => return Err(RestoreError::WrongRegion { state, region }),
+                Some(_) => {}
+            }
+        }
+
+        self.definition = definition.id;
+        self.store = store;
+        self.history.clear();
+        self.deferred.clear();
+        self.deferred_regions.clear();
+        self.raised.clear();
+        self.timers.clear();
+        self.entered_at = self.elapsed;
+        for (region, state) in states.into_iter().enumerate() {
+            let leaf = definition.initial_leaf_of(state);
+            self.set_region_state(region, leaf);
+
+            let active: Vec<State> = definition.ancestors(leaf).collect();
+            self.arm_timers(definition, region, &active);
+        }
+        Ok(())
+    }
+}
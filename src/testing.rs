@@ -0,0 +1,248 @@
+//! Model testing with seeded random walks.
+//!
+//! A [`RandomWalk`] sends random sequences of events to fresh machines and checks
+//! invariants on its state and store after every step. When an invariant breaks, the sequence
+//! of events is shrunk to a minimal one that still breaks it, and reported as a [`Failure`] that
+//! prints as Rust code.
+//!
+//! ```
+//! use fluent_state_machine::testing::RandomWalk;
+//! use fluent_state_machine::StateMachineBuilder;
+//!
+//! let turnstile = || {
+//!     StateMachineBuilder::new(0, "Locked")
+//!         .state("Locked")
+//!             .on("Coin").go_to("Unlocked").update(|coins| *coins += 1)
+//!         .state("Unlocked")
+//!             .on("Push").go_to("Locked")
+//!         .build()
+//! };
+//!
+//! RandomWalk::new(turnstile, ["Coin", "Push"])
+//!     .invariant("unlocked only after a coin", |state, coins| *state == "Locked" || *coins > 0)
+//!     .check();
+//! ```
+
+use std::fmt::{self, Debug};
+
+use crate::{Callbacks, Local, StateMachine};
+
+type Invariant<'a, State, Store> = Box<dyn Fn(&State, &Store) -> bool + 'a>;
+type Factory<'a, Event, State, Store, CallbackType> = Box<dyn Fn() -> StateMachine<Event, State, Store, CallbackType> + 'a>;
+
+/// Sends seeded random sequences of events to fresh machines and checks invariants after every
+/// step.
+#[must_use]
+pub struct RandomWalk<'a, Event, State, Store, CallbackType: Callbacks<Event, State, Store> = Local> {
+    machine: Factory<'a, Event, State, Store, CallbackType>,
+    alphabet: Vec<Event>,
+    invariants: Vec<(&'static str, Invariant<'a, State, Store>)>,
+    seed: u64,
+    walks: usize,
+    steps: usize,
+}
+
+impl<'a, Event, State, Store, CallbackType> RandomWalk<'a, Event, State, Store, CallbackType>
+where
+    CallbackType: Callbacks<Event, State, Store>,
+    State: Copy + PartialEq,
+    Event: PartialEq + Clone,
+{
+    /// Walks machines built by `machine`, picking every event from `alphabet`.
+    ///
+    /// Every walk, and every replay while shrinking, starts from a new machine. Copies of one
+    /// machine would share its definition, so an action that mutates what it captures would
+    /// carry that over from one walk to the next.
+    pub fn new(machine: impl Fn() -> StateMachine<Event, State, Store, CallbackType> + 'a, alphabet: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            machine: Box::new(machine),
+            alphabet: alphabet.into_iter().collect(),
+            invariants: Vec::new(),
+            seed: 0x5EED,
+            walks: 100,
+            steps: 50,
+        }
+    }
+
+    /// Sets the seed of the random number generator. The same seed gives the same walks.
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets how many walks to take, 100 by default.
+    pub const fn walks(mut self, walks: usize) -> Self {
+        self.walks = walks;
+        self
+    }
+
+    /// Sets how many events each walk sends, 50 by default.
+    pub const fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Adds an invariant that must hold in the initial state and after every event.
+    ///
+    /// The invariant is given `state`, the active state of the first region. In a machine with
+    /// orthogonal regions, the states of the other regions are not checked.
+    pub fn invariant(mut self, name: &'static str, holds: impl Fn(&State, &Store) -> bool + 'a) -> Self {
+        self.invariants.push((name, Box::new(holds)));
+        self
+    }
+
+    /// Takes every walk, stopping at the first one that breaks an invariant.
+    ///
+    /// # Errors
+    ///
+    /// Returns the shrunk sequence of events as a [`Failure`] if an invariant breaks.
+    pub fn run(&self) -> Result<(), Failure<Event, State>> {
+        let mut rng = XorShift::new(self.seed);
+        let steps = if self.alphabet.is_empty() { 0 } else { self.steps };
+
+        for _ in 0..self.walks {
+            let walk: Vec<usize> = (0..steps).map(|_| rng.below(self.alphabet.len())).collect();
+            if let Some((invariant, length)) = self.replay(&walk) {
+                let walk = self.shrink(walk[..length].to_vec(), invariant);
+                let mut machine = (self.machine)();
+                let events: Vec<Event> = walk.iter().map(|&event| self.alphabet[event].clone()).collect();
+                for event in &events {
+                    machine.trigger(event.clone());
+                }
+                return Err(Failure {
+                    invariant: self.invariants[invariant].0,
+                    seed: self.seed,
+                    events,
+                    state: machine.state,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`RandomWalk::run`], for use in tests.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the reproducer if an invariant breaks.
+    pub fn check(&self)
+    where
+        Event: Debug,
+        State: Debug,
+    {
+        if let Err(failure) = self.run() {
+            panic!("{failure}");
+        }
+    }
+
+    /// Sends the events at the given indices of the alphabet to a new machine, and returns
+    /// the first invariant to break together with the number of events sent when it broke.
+    fn replay(&self, walk: &[usize]) -> Option<(usize, usize)> {
+        let mut machine = (self.machine)();
+        let broken = |machine: &StateMachine<Event, State, Store, CallbackType>| {
+            self.invariants.iter().position(|(_, holds)| !holds(&machine.state, &machine.store))
+        };
+
+        if let Some(invariant) = broken(&machine) {
+            return Some((invariant, 0));
+        }
+        for (step, &event) in walk.iter().enumerate() {
+            machine.trigger(self.alphabet[event].clone());
+            if let Some(invariant) = broken(&machine) {
+                return Some((invariant, step + 1));
+            }
+        }
+        None
+    }
+
+    /// Removes ever smaller chunks of the walk for as long as it keeps breaking the same
+    /// invariant, until no single event can be removed.
+    fn shrink(&self, mut walk: Vec<usize>, invariant: usize) -> Vec<usize> {
+        let mut chunk = walk.len() / 2;
+        while chunk > 0 {
+            let length = walk.len();
+            let mut start = 0;
+            while start < walk.len() {
+                let end = (start + chunk).min(walk.len());
+                let candidate: Vec<usize> = walk[..start].iter().chain(&walk[end..]).copied().collect();
+                match self.replay(&candidate) {
+                    Some((broken, steps)) if broken == invariant => walk = candidate[..steps].to_vec(),
+                    _ => start += chunk,
+                }
+            }
+            if chunk > 1 || walk.len() == length {
+                chunk /= 2;
+            }
+        }
+        walk
+    }
+}
+
+/// A walk that broke an invariant, shrunk so that removing any event makes it pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure<Event, State> {
+    /// The name given to [`RandomWalk::invariant`].
+    pub invariant: &'static str,
+    pub seed: u64,
+    /// The events that break the invariant when sent to the machine in order.
+    pub events: Vec<Event>,
+    /// The state once `events` have been sent.
+    pub state: State,
+}
+
+/// Prints the failure as the Rust statements that reproduce it on a `machine` variable, naming
+/// enum variants by their full path, such as `Event::Forward`, so they compile without imports.
+impl<Event: Debug, State: Debug> fmt::Display for Failure<Event, State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "// invariant \"{}\" breaks in state {} (seed {})",
+            self.invariant,
+            path(&self.state),
+            self.seed
+        )?;
+        for event in &self.events {
+            writeln!(f, "machine.trigger({});", path(event))?;
+        }
+        Ok(())
+    }
+}
+
+/// The `Debug` output of `value`, prefixed with the name of its type when it looks like an enum
+/// variant rather than a literal or a struct.
+fn path<T: Debug>(value: &T) -> String {
+    let debug = format!("{value:?}");
+    let full_name = std::any::type_name::<T>();
+    let name = full_name.split('<').next().unwrap_or(full_name).rsplit("::").next().unwrap_or(full_name);
+    let is_struct = debug
+        .strip_prefix(name)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '(', '{']));
+    if debug.starts_with(char::is_uppercase) && !is_struct {
+        format!("{name}::{debug}")
+    } else {
+        debug
+    }
+}
+
+/// The xorshift64* generator: tiny, and the same on every platform, so seeds reproduce.
+struct XorShift(u64);
+
+impl XorShift {
+    const fn new(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeros.
+        Self(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    const fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..bound`.
+    #[allow(clippy::cast_possible_truncation)]
+    const fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{Callbacks, EventMatcher, MachineDefinition, MachineInstance, TriggerOutcome};
+
+/// A source of the current time for [`StateMachine::poll`](crate::StateMachine::poll).
+///
+/// Clocks are shared by every instance of a definition. A definition built with
+/// [`StateMachineBuilder::new_thread_safe`](crate::StateMachineBuilder::new_thread_safe) needs a
+/// `Send + Sync` clock.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// Reads the time from [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests of timed transitions.
+///
+/// Clones share the same time, so a test can keep one clone and hand another to the builder.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A pending timed transition of an active state.
+#[derive(Clone)]
+pub struct Timer {
+    pub transition: usize,
+    pub region: usize,
+    pub deadline: Duration,
+    /// Its guard rejected it during the current `advance`; it is checked again on the next one.
+    pub rejected: bool,
+}
+
+impl<Event, State, Store> MachineInstance<Event, State, Store>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Starts the timers of the timed transitions leaving any of `states`.
+    pub(crate) fn arm_timers<CallbackType: Callbacks<Event, State, Store>>(
+        &mut self,
+        definition: &MachineDefinition<Event, State, Store, CallbackType>,
+        region: usize,
+        states: &[State],
+    ) {
+        for index in definition.timers_leaving(states) {
+            if let Some(timeout) = definition.transitions[index].event.timeout() {
+                self.timers.push(Timer {
+                    transition: index,
+                    region,
+                    deadline: self.elapsed + timeout,
+                    rejected: false,
+                });
+            }
+        }
+    }
+
+    /// How long `state` has been active, in the machine's time moved by `advance` and `poll`.
+    ///
+    /// Every external transition of the first region starts it over, including one back to the
+    /// same state; internal transitions do not.
+    #[must_use]
+    pub const fn time_in_state(&self) -> Duration {
+        self.elapsed.saturating_sub(self.entered_at)
+    }
+
+    /// See [`StateMachine::advance`](crate::StateMachine::advance).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn advance<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, duration: Duration) -> Vec<TriggerOutcome<State>> {
+        self.expect_definition(definition);
+        let until = self.elapsed + duration;
+        let mut outcomes = Vec::new();
+        for timer in &mut self.timers {
+            timer.rejected = false;
+        }
+
+        while let Some(position) = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, timer)| !timer.rejected && timer.deadline <= until)
+            .min_by_key(|(_, timer)| timer.deadline)
+            .map(|(position, _)| position)
+        {
+            let mut timer = self.timers.remove(position);
+            // A timer rejected by an earlier advance is overdue and fires now, not in the past.
+            self.elapsed = self.elapsed.max(timer.deadline);
+            let outcome = self.fire_timer(definition, &timer);
+            if matches!(outcome, TriggerOutcome::Rejected { .. }) {
+                timer.rejected = true;
+                self.timers.push(timer);
+            }
+            outcomes.push(outcome);
+            outcomes.extend(self.run_to_completion(definition));
+        }
+
+        self.elapsed = until;
+        outcomes
+    }
+
+    /// See [`StateMachine::poll`](crate::StateMachine::poll).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `definition` is not the one this instance was created from.
+    pub fn poll<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>) -> Vec<TriggerOutcome<State>> {
+        let now = definition.clock.now();
+        let duration = now.saturating_duration_since(self.last_poll);
+        self.last_poll = now;
+        self.advance(definition, duration)
+    }
+
+    fn fire_timer<CallbackType: Callbacks<Event, State, Store>>(&mut self, definition: &MachineDefinition<Event, State, Store, CallbackType>, timer: &Timer) -> TriggerOutcome<State> {
+        let from = self.region_state(timer.region);
+        let transition = &definition.transitions[timer.transition];
+        debug_assert!(matches!(transition.event, EventMatcher::After(_)));
+
+        let guard_first = transition.guard_first;
+        if !guard_first {
+            self.run_before_event(definition, timer.transition, None);
+        }
+        if !definition.guard_passes(timer.transition, &self.store, None) {
+            return TriggerOutcome::Rejected { state: from, guards: 1 };
+        }
+        if guard_first {
+            self.run_before_event(definition, timer.transition, None);
+        }
+
+        let to = self.fire(definition, timer.region, timer.transition, None);
+        if from == to {
+            TriggerOutcome::SelfTransitioned { state: from }
+        } else {
+            TriggerOutcome::Transitioned { from, to }
+        }
+    }
+}
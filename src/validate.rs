@@ -0,0 +1,179 @@
+use std::fmt;
+
+use crate::{Callbacks, MachineDefinition};
+
+/// A mistake in a state machine definition, reported by `StateMachineBuilder::try_build`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError<Event, State> {
+    /// An unguarded transition for a (state, event) pair that already has an unguarded transition.
+    DuplicateTransition { state: State, event: Event },
+    /// A guarded transition that can never fire because an earlier unguarded one always wins.
+    ShadowedTransition { state: State, event: Event },
+    /// A transition targets a state that was never declared with `.state()`.
+    UndeclaredTarget { from: State, to: State },
+    /// The initial state was never declared with `.state()`.
+    UndeclaredInitialState { state: State },
+    /// A declared state that no sequence of transitions leads to from the initial state.
+    UnreachableState { state: State },
+    /// A state is declared as a substate of a parent that was never declared with `.state()`.
+    UndeclaredParent { state: State, parent: State },
+    /// Following `.substate_of()` from this state leads back to the state itself.
+    CyclicHierarchy { state: State },
+    /// The state chosen with `.initial_substate()` is not a direct substate of the composite state.
+    InvalidInitialSubstate { state: State, substate: State },
+    /// A transition leads from a state in one orthogonal region to a state in another.
+    CrossRegionTransition { from: State, to: State },
+    /// A state is declared as a substate of a parent that belongs to another orthogonal region.
+    CrossRegionSubstate { state: State, parent: State },
+    /// The initial state given to `.region()` is declared in another orthogonal region.
+    CrossRegionInitialState { state: State, region: usize },
+}
+
+impl<Event: fmt::Debug, State: fmt::Debug> fmt::Display for BuildError<Event, State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateTransition { state, event } => {
+                write!(f, "duplicate unguarded transition from {state:?} on {event:?}")
+            }
+            Self::ShadowedTransition { state, event } => {
+                write!(f, "transition from {state:?} on {event:?} is shadowed by an earlier unguarded transition")
+            }
+            Self::UndeclaredTarget { from, to } => {
+                write!(f, "transition from {from:?} targets undeclared state {to:?}")
+            }
+            Self::UndeclaredInitialState { state } => {
+                write!(f, "initial state {state:?} is never declared")
+            }
+            Self::UnreachableState { state } => {
+                write!(f, "state {state:?} is unreachable from the initial state")
+            }
+            Self::UndeclaredParent { state, parent } => {
+                write!(f, "state {state:?} is a substate of undeclared state {parent:?}")
+            }
+            Self::CyclicHierarchy { state } => {
+                write!(f, "state {state:?} is its own ancestor")
+            }
+            Self::InvalidInitialSubstate { state, substate } => {
+                write!(f, "initial substate {substate:?} of {state:?} is not one of its substates")
+            }
+            Self::CrossRegionTransition { from, to } => {
+                write!(f, "transition from {from:?} to {to:?} crosses orthogonal regions")
+            }
+            Self::CrossRegionSubstate { state, parent } => {
+                write!(f, "state {state:?} and its parent {parent:?} belong to different regions")
+            }
+            Self::CrossRegionInitialState { state, region } => {
+                write!(f, "initial state {state:?} of region {region} is declared in another region")
+            }
+        }
+    }
+}
+
+impl<Event: fmt::Debug, State: fmt::Debug> std::error::Error for BuildError<Event, State> {}
+
+pub fn validate<Event, State, Store, CallbackType: Callbacks<Event, State, Store>>(
+    definition: &MachineDefinition<Event, State, Store, CallbackType>,
+) -> Vec<BuildError<Event, State>>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq + Clone,
+{
+    let mut errors = Vec::new();
+    let transitions = &definition.transitions;
+
+    let initial_states = definition.initial_states.clone();
+    let region_of = |state: State| {
+        definition
+            .states
+            .iter()
+            .find(|node| node.state == state)
+            .map(|node| node.region)
+    };
+
+    for (region, &state) in initial_states.iter().enumerate() {
+        if !definition.is_declared(state) {
+            errors.push(BuildError::UndeclaredInitialState { state });
+        } else if region_of(state) != Some(region) {
+            errors.push(BuildError::CrossRegionInitialState { state, region });
+        }
+    }
+
+    for (index, transition) in transitions.iter().enumerate() {
+        let shadowed_by_earlier = transitions[..index].iter().any(|earlier| {
+            earlier.from_state == transition.from_state
+                && earlier.event.covers(&transition.event)
+                && earlier.condition.is_none()
+        });
+
+        if let Some(event) = transition.event.event().filter(|_| shadowed_by_earlier) {
+            let state = transition.from_state;
+            let event = event.clone();
+            errors.push(if transition.condition.is_none() {
+                BuildError::DuplicateTransition { state, event }
+            } else {
+                BuildError::ShadowedTransition { state, event }
+            });
+        }
+
+        if !definition.is_declared(transition.target()) {
+            errors.push(BuildError::UndeclaredTarget {
+                from: transition.from_state,
+                to: transition.target(),
+            });
+        } else if region_of(transition.from_state) != region_of(transition.target()) {
+            errors.push(BuildError::CrossRegionTransition {
+                from: transition.from_state,
+                to: transition.target(),
+            });
+        }
+    }
+
+    for node in &definition.states {
+        if let Some(parent) = node.parent {
+            if !definition.is_declared(parent) {
+                errors.push(BuildError::UndeclaredParent { state: node.state, parent });
+            } else if region_of(parent) != Some(node.region) {
+                errors.push(BuildError::CrossRegionSubstate { state: node.state, parent });
+            }
+        }
+        if definition.ancestors(node.state).skip(1).any(|ancestor| ancestor == node.state) {
+            errors.push(BuildError::CyclicHierarchy { state: node.state });
+        }
+        if let Some(substate) = node.initial_substate {
+            if definition.parent_of(substate) != Some(node.state) {
+                errors.push(BuildError::InvalidInitialSubstate { state: node.state, substate });
+            }
+        }
+    }
+
+    // Entering a state also enters its ancestors and, for composite states, the initial substates.
+    let entered = |state: State| {
+        definition.ancestors(state).chain(definition.initial_substates(state))
+    };
+
+    let mut reachable: Vec<State> = Vec::new();
+    for state in initial_states.into_iter().flat_map(entered) {
+        if !reachable.contains(&state) {
+            reachable.push(state);
+        }
+    }
+    let mut next = 0;
+    while let Some(&state) = reachable.get(next) {
+        for transition in transitions.iter().filter(|t| t.from_state == state) {
+            for state in entered(transition.target()) {
+                if !reachable.contains(&state) {
+                    reachable.push(state);
+                }
+            }
+        }
+        next += 1;
+    }
+
+    for node in &definition.states {
+        if !reachable.contains(&node.state) {
+            errors.push(BuildError::UnreachableState { state: node.state });
+        }
+    }
+
+    errors
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use crate::{Callbacks, EventMatcher, History, MachineDefinition, StateNode, Transition};
+
+/// What makes a transition fire, mirroring the builder method that declared it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransitionTrigger<'a, Event> {
+    /// Declared with `.on()`: fires for events equal to this one.
+    On(&'a Event),
+    /// Declared with `.on_kind()`: fires for every event of the same kind.
+    OnKind(&'a Event),
+    /// Declared with `.after()`: fires once the state has been active this long.
+    After(Duration),
+}
+
+impl<Event> Clone for TransitionTrigger<'_, Event> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Event> Copy for TransitionTrigger<'_, Event> {}
+
+impl<'a, Event> TransitionTrigger<'a, Event> {
+    /// The event the transition reacts to, or `None` for a timed transition.
+    #[must_use]
+    pub const fn event(&self) -> Option<&'a Event> {
+        match self {
+            Self::On(event) | Self::OnKind(event) => Some(event),
+            Self::After(_) => None,
+        }
+    }
+
+    const fn new(matcher: &'a EventMatcher<Event>) -> Self {
+        match matcher {
+            EventMatcher::Exact(event) => Self::On(event),
+            EventMatcher::Kind(event, _) => Self::OnKind(event),
+            EventMatcher::After(timeout) => Self::After(*timeout),
+        }
+    }
+}
+
+/// A read-only description of one transition of a built machine, see
+/// [`MachineDefinition::transitions`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct TransitionView<'a, Event, State> {
+    /// The state the transition was declared on.
+    pub from: State,
+    /// The state given to `.go_to()`, or `from` for an internal transition.
+    pub to: State,
+    pub trigger: TransitionTrigger<'a, Event>,
+    /// A transition without `.go_to()` runs its actions without leaving the state.
+    pub internal: bool,
+    /// How the target is entered when declared with `.go_to_history()`.
+    pub history: Option<History>,
+    /// Whether the transition has an `only_if` guard.
+    pub guarded: bool,
+    /// The name given to `.only_if_named()`.
+    pub guard_name: Option<&'static str>,
+    /// Whether the transition has an `update` or `then` action.
+    pub has_action: bool,
+}
+
+impl<Event, State: Copy> Clone for TransitionView<'_, Event, State> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Event, State: Copy> Copy for TransitionView<'_, Event, State> {}
+
+impl<'a, Event, State: Copy> TransitionView<'a, Event, State> {
+    fn new<Store, CallbackType: Callbacks<Event, State, Store>>(transition: &'a Transition<Event, State, Store, CallbackType>) -> Self {
+        Self {
+            from: transition.from_state,
+            to: transition.target(),
+            trigger: TransitionTrigger::new(&transition.event),
+            internal: transition.to_state.is_none(),
+            history: transition.history,
+            guarded: transition.condition.is_some(),
+            guard_name: transition.guard_name,
+            has_action: transition.before_event.is_some() || transition.after_event.is_some(),
+        }
+    }
+}
+
+/// A read-only description of one declared state of a built machine, see
+/// [`MachineDefinition::states`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateView<'a, Event, State> {
+    pub state: State,
+    /// The orthogonal region the state belongs to, 0 for the states before the first `.region()`.
+    pub region: usize,
+    /// The state given to `.substate_of()`.
+    pub parent: Option<State>,
+    /// The substate entered with the state, if it is a composite state.
+    pub initial_substate: Option<State>,
+    /// Whether the state has an `on_enter` action.
+    pub has_entry_action: bool,
+    /// Whether the state has an `on_exit` action.
+    pub has_exit_action: bool,
+    /// Events postponed with `.defer()` or `.defer_kind()` while the state is active.
+    pub deferred: Vec<TransitionTrigger<'a, Event>>,
+}
+
+impl<Event, State, Store, CallbackType: Callbacks<Event, State, Store>> MachineDefinition<Event, State, Store, CallbackType>
+where
+    State: Copy + PartialEq,
+    Event: PartialEq,
+{
+    /// Every transition, in the order it was declared.
+    pub fn transitions(&self) -> impl Iterator<Item = TransitionView<'_, Event, State>> {
+        self.transitions.iter().map(TransitionView::new)
+    }
+
+    /// The transitions declared on `state` itself, not those inherited from its parent states.
+    pub fn transitions_from(&self, state: State) -> impl Iterator<Item = TransitionView<'_, Event, State>> {
+        self.transitions().filter(move |transition| transition.from == state)
+    }
+
+    /// The states with a transition leading into `state`, each listed once. Internal
+    /// transitions do not enter a state and are not counted.
+    #[must_use]
+    pub fn predecessors_of(&self, state: State) -> Vec<State> {
+        let mut predecessors = Vec::new();
+        for transition in self.transitions().filter(|transition| transition.to == state && !transition.internal) {
+            if !predecessors.contains(&transition.from) {
+                predecessors.push(transition.from);
+            }
+        }
+        predecessors
+    }
+
+    /// Every state declared with `.state()`, in declaration order.
+    pub fn states(&self) -> impl Iterator<Item = StateView<'_, Event, State>> {
+        self.states.iter().map(|node| self.state_view(node))
+    }
+
+    /// Every event that a transition or `.defer()` reacts to, each listed once, in the order
+    /// they first appear. Timed transitions have no event and are not included.
+    #[must_use]
+    pub fn events(&self) -> Vec<&Event> {
+        let transition_events = self.transitions.iter().filter_map(|transition| transition.event.event());
+        let deferred_events = self.states.iter().flat_map(|node| node.deferred.iter().filter_map(EventMatcher::event));
+
+        let mut events: Vec<&Event> = Vec::new();
+        for event in transition_events.chain(deferred_events) {
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn state_view<'a>(&self, node: &'a StateNode<Event, State, Store, CallbackType>) -> StateView<'a, Event, State> {
+        StateView {
+            state: node.state,
+            region: node.region,
+            parent: node.parent,
+            initial_substate: self.initial_substate_of(node.state),
+            has_entry_action: node.on_enter.is_some(),
+            has_exit_action: node.on_exit.is_some(),
+            deferred: node.deferred.iter().map(TransitionTrigger::new).collect(),
+        }
+    }
+
+    /// The state each region starts in, in the order the regions were declared.
+    #[must_use]
+    pub fn initial_states(&self) -> &[State] {
+        &self.initial_states
+    }
+}
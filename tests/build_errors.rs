@@ -0,0 +1,168 @@
+use fluent_state_machine::{BuildError, EventKind, StateMachineBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum States {
+    Idle,
+    Running,
+    Done,
+    Orphan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Events {
+    Start,
+    Finish,
+    Reset,
+    Progress(u8),
+}
+
+impl EventKind for Events {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Events::{Finish, Progress, Reset, Start};
+    use States::{Done, Idle, Orphan, Running};
+
+    #[test]
+    fn test_valid_definition() {
+        let result = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .on(Start).go_to(Running)
+            .state(Running)
+                .on(Finish).go_to(Done)
+            .state(Done)
+            .try_build();
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_and_shadowed_transitions() {
+        let errors = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .on(Start).go_to(Running)
+                .on(Start).go_to(Done)
+                .on(Start).go_to(Done).only_if(|()| true)
+            .state(Running)
+            .state(Done)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                BuildError::DuplicateTransition { state: Idle, event: Start },
+                BuildError::ShadowedTransition { state: Idle, event: Start },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_guarded_alternatives_are_valid() {
+        let result = StateMachineBuilder::new(0, Idle)
+            .state(Idle)
+                .on(Start).go_to(Running).only_if(|n| *n > 0)
+                .on(Start).go_to(Done)
+            .state(Running)
+            .state(Done)
+            .try_build();
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_event_kind_shadows_exact_events() {
+        let errors = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .on(Progress(100)).go_to(Done)
+                .on_kind(Progress(0))
+                .on(Progress(50))
+            .state(Done)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(errors, vec![BuildError::DuplicateTransition { state: Idle, event: Progress(50) }]);
+    }
+
+    #[test]
+    fn test_undeclared_states() {
+        let errors = StateMachineBuilder::new((), Idle)
+            .state(Running)
+                .on(Finish).go_to(Done)
+                .on(Reset).go_to(Idle)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                BuildError::UndeclaredInitialState { state: Idle },
+                BuildError::UndeclaredTarget { from: Running, to: Done },
+                BuildError::UndeclaredTarget { from: Running, to: Idle },
+                BuildError::UnreachableState { state: Running },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_state() {
+        let errors = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .on(Start).go_to(Running)
+            .state(Running)
+                .on(Reset).go_to(Idle)
+            .state(Orphan)
+                .on(Reset).go_to(Idle)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(errors, vec![BuildError::UnreachableState { state: Orphan }]);
+        assert_eq!(errors[0].to_string(), "state Orphan is unreachable from the initial state");
+    }
+
+    #[test]
+    fn test_hierarchy_errors() {
+        let errors = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .substate_of(Done)
+                .on(Start).go_to(Running)
+            .state(Running)
+                .substate_of(Orphan)
+                .initial_substate(Done)
+            .state(Orphan)
+                .substate_of(Running)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                BuildError::UndeclaredParent { state: Idle, parent: Done },
+                BuildError::CyclicHierarchy { state: Running },
+                BuildError::InvalidInitialSubstate { state: Running, substate: Done },
+                BuildError::CyclicHierarchy { state: Orphan },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_substates_of_reachable_composite_are_reachable() {
+        let result = StateMachineBuilder::new((), Idle)
+            .state(Idle)
+                .on(Start).go_to(Running)
+            .state(Running)
+                .on(Reset).go_to(Idle)
+            .state(Done)
+                .substate_of(Running)
+            .try_build();
+
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+use fluent_state_machine::{History, StateMachine, StateMachineBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Stopped,
+    Paused,
+    Playback,
+    Normal,
+    Shuffle,
+    ShuffleAll,
+    ShuffleAlbum,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Play,
+    Pause,
+    Stop,
+    Resume,
+    ResumeShallow,
+    ToggleShuffle,
+    ToggleScope,
+}
+
+fn create_cd_player() -> StateMachine<Event, State, ()> {
+    use Event::*;
+    use State::*;
+
+    StateMachineBuilder::new((), Stopped)
+        .state(Stopped)
+            .on(Play).go_to(Playback)
+        .state(Paused)
+            .on(Resume).go_to_history(Playback, History::Deep)
+            .on(ResumeShallow).go_to_history(Playback, History::Shallow)
+            .on(Stop).go_to(Stopped)
+        .state(Playback)
+            .on(Pause).go_to(Paused)
+            .on(Stop).go_to(Stopped)
+        .state(Normal)
+            .substate_of(Playback)
+            .on(ToggleShuffle).go_to(Shuffle)
+        .state(Shuffle)
+            .substate_of(Playback)
+            .on(ToggleShuffle).go_to(Normal)
+        .state(ShuffleAll)
+            .substate_of(Shuffle)
+            .on(ToggleScope).go_to(ShuffleAlbum)
+        .state(ShuffleAlbum)
+            .substate_of(Shuffle)
+            .on(ToggleScope).go_to(ShuffleAll)
+        .try_build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_enters_initial_substate() {
+        let mut cd = create_cd_player();
+        cd.trigger(Event::Play);
+        assert_eq!(cd.state, State::Normal);
+        assert!(cd.is_in(State::Playback));
+    }
+
+    #[test]
+    fn test_deep_history_resumes_innermost_state() {
+        let mut cd = create_cd_player();
+        cd.trigger(Event::Play);
+        cd.trigger(Event::ToggleShuffle);
+        cd.trigger(Event::ToggleScope);
+        assert_eq!(cd.state, State::ShuffleAlbum);
+
+        cd.trigger(Event::Pause);
+        assert_eq!(cd.state, State::Paused);
+
+        cd.trigger(Event::Resume);
+        assert_eq!(cd.state, State::ShuffleAlbum);
+    }
+
+    #[test]
+    fn test_shallow_history_resumes_direct_substate() {
+        let mut cd = create_cd_player();
+        cd.trigger(Event::Play);
+        cd.trigger(Event::ToggleShuffle);
+        cd.trigger(Event::ToggleScope);
+        cd.trigger(Event::Pause);
+
+        cd.trigger(Event::ResumeShallow);
+        assert_eq!(cd.state, State::ShuffleAll);
+        assert!(cd.is_in(State::Shuffle));
+    }
+
+    #[test]
+    fn test_plain_go_to_ignores_history() {
+        let mut cd = create_cd_player();
+        cd.trigger(Event::Play);
+        cd.trigger(Event::ToggleShuffle);
+        cd.trigger(Event::Stop);
+
+        cd.trigger(Event::Play);
+        assert_eq!(cd.state, State::Normal);
+
+        cd.trigger(Event::Pause);
+        cd.trigger(Event::Resume);
+        assert_eq!(cd.state, State::Normal);
+    }
+}
@@ -0,0 +1,184 @@
+use fluent_state_machine::{BuildError, StateMachine, StateMachineBuilder, TriggerOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    PoweredOff,
+    PoweredOn,
+    TrayClosed,
+    TrayOpen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Power,
+    Eject,
+}
+
+#[derive(Default)]
+struct Store {
+    powered: bool,
+    log: Vec<&'static str>,
+}
+
+fn create_cd_player() -> StateMachine<Event, State, Store> {
+    use Event::{Eject, Power};
+    use State::{PoweredOff, PoweredOn, TrayClosed, TrayOpen};
+
+    StateMachineBuilder::new(Store::default(), PoweredOff)
+        .state(PoweredOff)
+            .on(Power).go_to(PoweredOn).then(|store| {
+                store.powered = true;
+                store.log.push("power on");
+            })
+        .state(PoweredOn)
+            .on(Power).go_to(PoweredOff).then(|store| {
+                store.powered = false;
+                store.log.push("power off");
+            })
+    .region(TrayClosed)
+        .state(TrayClosed)
+            .on(Eject).go_to(TrayOpen).only_if(|store| store.powered)
+        .state(TrayOpen)
+            .on(Eject).go_to(TrayClosed)
+            .on(Power).go_to(TrayClosed).only_if(|store| !store.powered).then(|store| {
+                store.log.push("close tray");
+            })
+        .try_build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regions_start_in_their_initial_states() {
+        let cd = create_cd_player();
+        assert_eq!(cd.state, State::PoweredOff);
+        assert_eq!(cd.configuration(), vec![State::PoweredOff, State::TrayClosed]);
+        assert!(cd.is_in(State::TrayClosed));
+    }
+
+    #[test]
+    fn test_event_is_dispatched_to_every_region() {
+        let mut cd = create_cd_player();
+
+        cd.trigger(Event::Eject);
+        assert_eq!(cd.configuration(), vec![State::PoweredOff, State::TrayClosed]);
+
+        cd.trigger(Event::Power);
+        cd.trigger(Event::Eject);
+        assert_eq!(cd.configuration(), vec![State::PoweredOn, State::TrayOpen]);
+
+        // The power region runs first, so the tray region already sees the player powered off
+        let outcomes = cd.trigger_regions(Event::Power);
+        assert_eq!(
+            outcomes,
+            vec![
+                TriggerOutcome::Transitioned { from: State::PoweredOn, to: State::PoweredOff },
+                TriggerOutcome::Transitioned { from: State::TrayOpen, to: State::TrayClosed },
+            ]
+        );
+        assert_eq!(cd.store.log, ["power on", "power off", "close tray"]);
+    }
+
+    #[test]
+    fn test_trigger_reports_the_region_that_fired() {
+        let mut cd = create_cd_player();
+        cd.trigger(Event::Power);
+
+        assert_eq!(
+            cd.trigger(Event::Eject),
+            TriggerOutcome::Transitioned { from: State::TrayClosed, to: State::TrayOpen }
+        );
+    }
+
+    #[test]
+    fn test_transitions_cannot_cross_regions() {
+        let errors = StateMachineBuilder::new((), State::PoweredOff)
+            .state(State::PoweredOff)
+                .on(Event::Eject).go_to(State::TrayOpen)
+        .region(State::TrayClosed)
+            .state(State::TrayClosed)
+                .on(Event::Eject).go_to(State::TrayOpen)
+            .state(State::TrayOpen)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![BuildError::CrossRegionTransition { from: State::PoweredOff, to: State::TrayOpen }]
+        );
+    }
+
+    #[test]
+    fn test_region_initial_state_must_belong_to_the_region() {
+        let errors = StateMachineBuilder::new((), State::PoweredOff)
+            .state(State::PoweredOff)
+                .on(Event::Power).go_to(State::PoweredOn)
+            .state(State::PoweredOn)
+                .on(Event::Power).go_to(State::PoweredOff)
+        .region(State::PoweredOff)
+            .state(State::TrayClosed)
+                .on(Event::Eject).go_to(State::TrayOpen)
+            .state(State::TrayOpen)
+                .on(Event::Eject).go_to(State::TrayClosed)
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                BuildError::CrossRegionInitialState { state: State::PoweredOff, region: 1 },
+                BuildError::UnreachableState { state: State::TrayClosed },
+                BuildError::UnreachableState { state: State::TrayOpen },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deferral_is_decided_per_region() {
+        use Event::{Eject, Power};
+        use State::{PoweredOff, PoweredOn, TrayClosed, TrayOpen};
+
+        let mut cd = StateMachineBuilder::new(Store::default(), PoweredOff)
+            .state(PoweredOff)
+                .on(Power).go_to(PoweredOn).then(|store| store.log.push("power on"))
+            .state(PoweredOn)
+                .on(Power).go_to(PoweredOff).then(|store| store.log.push("power off"))
+        .region(TrayOpen)
+            .state(TrayOpen)
+                .defer(Power)
+                .on(Eject).go_to(TrayClosed)
+            .state(TrayClosed)
+                .on(Power).then(|store| store.log.push("tray saw power"))
+            .build();
+
+        // Only the tray region defers the event; the power region moves right away
+        assert_eq!(
+            cd.trigger_regions(Power),
+            vec![TriggerOutcome::Transitioned { from: PoweredOff, to: PoweredOn }, TriggerOutcome::Deferred { state: TrayOpen }]
+        );
+        assert_eq!(cd.deferred_events(), [Power]);
+
+        // Closing the tray replays the event to the tray region only
+        cd.trigger(Eject);
+        assert_eq!(cd.configuration(), vec![PoweredOn, TrayClosed]);
+        assert_eq!(cd.store.log, ["power on", "tray saw power"]);
+        assert!(cd.deferred_events().is_empty());
+    }
+
+    #[test]
+    fn test_trigger_reports_deferred_when_no_region_fires() {
+        let mut cd = StateMachineBuilder::new(Store::default(), State::PoweredOff)
+            .state(State::PoweredOff)
+        .region(State::TrayOpen)
+            .state(State::TrayOpen)
+                .defer(Event::Power)
+            .build();
+
+        assert_eq!(cd.trigger(Event::Power), TriggerOutcome::Deferred { state: State::TrayOpen });
+    }
+}
@@ -1,4 +1,6 @@
-use fluent_state_machine::{StateMachine, StateMachineBuilder};
+use std::cell::Cell;
+use std::rc::Rc;
+use fluent_state_machine::{StateMachine, StateMachineBuilder, TriggerOutcome};
 
 fn create_cd_player() -> StateMachine<&'static str , &'static str, i32> {
 
@@ -34,6 +36,48 @@ mod tests {
         assert_eq!(cd.store, 0);          
     }
 
+    #[test]
+    fn test_forward_is_self_transition() {
+        let mut cd = create_cd_player();
+        assert_eq!(cd.trigger("Forward"), TriggerOutcome::SelfTransitioned { state: "Stopped" });
+        assert_eq!(cd.trigger("Play"), TriggerOutcome::Transitioned { from: "Stopped", to: "Playing" });
+    }
+
+    #[test]
+    fn test_permitted_events() {
+        let mut cd = create_cd_player();
+        assert_eq!(cd.permitted_events(), [&"Forward", &"Backward"]);
+        assert!(!cd.can_trigger(&"Play"));
+
+        cd.trigger("Forward");
+        assert!(cd.can_trigger(&"Play"));
+        assert_eq!(cd.store, 1);
+
+        cd.trigger("Play");
+        assert_eq!(cd.permitted_events(), [&"Stop", &"Pause"]);
+    }
+
+    #[test]
+    fn test_can_trigger_runs_no_actions() {
+        let updates = Rc::new(Cell::new(0));
+        let counted = Rc::clone(&updates);
+        let turnstile = StateMachineBuilder::new(1, "Locked")
+            .state("Locked")
+                .on("Coin").update(move |coins| {
+                    counted.set(counted.get() + 1);
+                    *coins += 1;
+                }).only_if(|coins| *coins >= 2)
+                .on("Kick").go_to("Broken").only_if(|coins| *coins >= 2).update(|coins| *coins = 0).guard_first()
+            .build();
+
+        // The guard of `Coin` would see its update, so it may fire; `Kick` checks its guard
+        assert!(turnstile.can_trigger(&"Coin"));
+        assert!(!turnstile.can_trigger(&"Kick"));
+        assert_eq!(turnstile.permitted_events(), [&"Coin"]);
+        assert_eq!(updates.get(), 0);
+        assert_eq!(turnstile.store, 1);
+    }
+
     #[test]    
     fn test_press_forward_when_playing() {
         let mut cd = create_cd_player();
@@ -0,0 +1,243 @@
+use fluent_state_machine::{EventKind, StateMachine, StateMachineBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum States {
+    Locked,
+    Unlocked,
+    Broken,
+}
+
+#[derive(Debug, PartialEq)]
+enum Events {
+    Coin(u32),
+    Push,
+    Kick,
+}
+
+impl EventKind for Events {}
+
+// The turnstile and CD player from the README, whose diagrams are checked in under `docs/`.
+fn create_readme_turnstile() -> StateMachine<&'static str, &'static str, ()> {
+    StateMachineBuilder::new((), "Locked")
+        .state("Locked")
+            .on("Coin").go_to("Unlocked")
+        .state("Unlocked")
+            .on("Push").go_to("Locked")
+        .build()
+}
+
+fn create_readme_cd_player() -> StateMachine<&'static str, &'static str, i32> {
+    StateMachineBuilder::new(0, "Stopped")
+        .state("Stopped")
+            .on("Play").go_to("Playing").only_if(|track| *track > 0)
+            .on("Forward").update(|track| *track += 1)
+            .on("Backward").update(|track| *track -= 1)
+        .state("Playing")
+            .on("Stop").go_to("Stopped").then(|track| *track = 0)
+            .on("Pause").go_to("Paused")
+        .state("Paused")
+            .on("Play").go_to("Playing")
+            .on("Stop").go_to("Stopped").then(|track| *track = 0)
+            .on("Forward").update(|track| *track += 1)
+            .on("Backward").update(|track| *track -= 1)
+        .build()
+}
+
+fn create_turnstile() -> StateMachine<Events, States, u32> {
+    use Events::{Coin, Kick, Push};
+    use States::{Broken, Locked, Unlocked};
+
+    StateMachineBuilder::new(0, Locked)
+        .state(Locked)
+            .on_kind(Coin(0)).go_to(Unlocked).only_if_named("not full", |coins| *coins < 100).update(|coins| *coins += 1)
+            .on(Kick).go_to(Broken)
+        .state(Unlocked)
+            .on(Push).go_to(Locked)
+            .on_kind(Coin(0)).update(|coins| *coins += 1)
+        .state(Broken)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_dot() {
+        let mut turnstile = create_turnstile();
+        turnstile.trigger(Events::Coin(1));
+
+        let expected = r#"digraph {
+    compound=true;
+    start0 [shape=point];
+    "Locked";
+    "Unlocked" [style=filled, fillcolor=lightgrey];
+    "Broken";
+    start0 -> "Locked";
+    "Locked" -> "Unlocked" [label="Coin(..) [not full] / action"];
+    "Locked" -> "Broken" [label="Kick"];
+    "Unlocked" -> "Locked" [label="Push"];
+    "Unlocked" -> "Unlocked" [label="Coin(..) / action", style=dashed];
+}
+"#;
+        assert_eq!(turnstile.to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_dot_draws_composite_states_as_clusters() {
+        let machine = StateMachineBuilder::new((), States::Broken)
+            .state(States::Broken)
+                .on(Events::Kick).go_to(States::Broken)
+            .state(States::Locked)
+                .substate_of(States::Broken)
+            .build();
+
+        let expected = r#"digraph {
+    compound=true;
+    start0 [shape=point];
+    subgraph "cluster_Broken" {
+        label="Broken";
+        "Locked" [style=filled, fillcolor=lightgrey];
+    }
+    start0 -> "Locked" [lhead="cluster_Broken"];
+    "Locked" -> "Locked" [label="Kick", ltail="cluster_Broken", lhead="cluster_Broken"];
+}
+"#;
+        assert_eq!(machine.to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_dot_labels_strings_without_quotes() {
+        let machine = StateMachineBuilder::new((), "Idle")
+            .state("Idle")
+                .on("Start").go_to("Running")
+            .state("Running")
+                .substate_of("Idle")
+            .build();
+
+        let expected = r#"digraph {
+    compound=true;
+    start0 [shape=point];
+    subgraph "cluster_Idle" {
+        label="Idle";
+        "Running" [style=filled, fillcolor=lightgrey];
+    }
+    start0 -> "Running" [lhead="cluster_Idle"];
+    "Running" -> "Running" [label="Start", ltail="cluster_Idle"];
+}
+"#;
+        assert_eq!(machine.to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let expected = "\
+stateDiagram-v2
+    [*] --> Locked
+    Locked
+    Unlocked
+    Broken
+    Locked --> Unlocked : Coin(..) [not full] / action
+    Locked --> Broken : Kick
+    Unlocked --> Locked : Push
+    Unlocked --> Unlocked : Coin(..) / action
+";
+        assert_eq!(create_turnstile().to_mermaid(), expected);
+    }
+
+    #[test]
+    fn test_to_mermaid_nests_composite_states() {
+        let machine = StateMachineBuilder::new((), "Operational")
+            .state("Operational")
+                .on("Break").go_to("Broken")
+            .state("Idle")
+                .substate_of("Operational")
+            .state("Broken")
+                .on("Repair").go_to("Operational").only_if(|()| true)
+            .build();
+
+        let expected = "\
+stateDiagram-v2
+    [*] --> Operational
+    state Operational {
+        [*] --> Idle
+        Idle
+    }
+    Broken
+    Operational --> Broken : Break
+    Broken --> Operational : Repair [guarded]
+";
+        assert_eq!(machine.to_mermaid(), expected);
+    }
+
+    #[test]
+    fn test_colliding_identifiers_get_suffixes() {
+        let machine = StateMachineBuilder::new((), "On Off")
+            .state("On Off")
+                .on("Toggle").go_to("OnOff")
+            .state("OnOff")
+            .build();
+
+        let expected = "\
+stateDiagram-v2
+    state \"On Off\" as OnOff_2
+    [*] --> OnOff_2
+    OnOff_2
+    OnOff
+    OnOff_2 --> OnOff : Toggle
+";
+        assert_eq!(machine.to_mermaid(), expected);
+
+        let expected = "\
+@startuml
+state \"On Off\" as OnOff_2
+[*] --> OnOff_2
+state OnOff_2
+state OnOff
+OnOff_2 --> OnOff : Toggle
+@enduml
+";
+        assert_eq!(machine.to_plantuml(), expected);
+    }
+
+    #[test]
+    fn test_to_plantuml() {
+        let machine = StateMachineBuilder::new(0, States::Broken)
+            .state(States::Broken)
+                .on_enter(|coins| *coins = 0)
+                .defer_kind(Events::Coin(0))
+                .on(Events::Kick).go_to(States::Broken)
+            .state(States::Locked)
+                .substate_of(States::Broken)
+                .on(Events::Push).go_to(States::Unlocked).only_if(|coins| *coins > 0)
+            .state(States::Unlocked)
+                .substate_of(States::Broken)
+                .on_exit(|coins| *coins -= 1)
+                .on(Events::Push).update(|coins| *coins += 1)
+            .build();
+
+        let expected = "\
+@startuml
+[*] --> Broken
+state Broken {
+  [*] --> Locked
+  state Locked
+  state Unlocked
+}
+Broken : entry / action
+Broken : Coin(..) / defer
+Unlocked : exit / action
+Broken --> Broken : Kick
+Locked --> Unlocked : Push [guarded]
+Unlocked : Push / action
+@enduml
+";
+        assert_eq!(machine.to_plantuml(), expected);
+    }
+
+    #[test]
+    fn test_readme_diagrams_are_up_to_date() {
+        assert_eq!(create_readme_turnstile().to_mermaid(), include_str!("../docs/turnstile.mmd"));
+        assert_eq!(create_readme_cd_player().to_mermaid(), include_str!("../docs/cd_player.mmd"));
+    }
+}
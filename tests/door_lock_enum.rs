@@ -1,5 +1,5 @@
 use std::time::Duration;
-use fluent_state_machine::{StateMachine, StateMachineBuilder};
+use fluent_state_machine::{DEFAULT_MAX_STEPS, ManualClock, StateMachine, StateMachineBuilder, TriggerOutcome};
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -10,7 +10,7 @@ enum States {
     Unlocking
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 enum Event {
     OpenDoor,
     Step
@@ -30,24 +30,18 @@ enum DoorSensor {
 struct Store {
     lock_sensor: LockSensor,
     door_sensor: DoorSensor,
-    duration_in_state: Duration,
 }
 
 
 
-
-fn create_lock() -> StateMachine<Event, States, Store> {
-    // Create a u32 store for state machine. It could be any type you want
+fn create_timed_lock(clock: ManualClock) -> StateMachine<Event, States, Store> {
     let store = Store {
         lock_sensor: LockSensor::Locked,
         door_sensor: DoorSensor::Closed,
-        duration_in_state: Duration::from_secs(0),
     };
 
-
-    // Construct state machine.
-    StateMachineBuilder::new( store, States::Locked)
-    .set_global_action(|store, _, _| store.duration_in_state = Duration::from_secs(0))
+    StateMachineBuilder::new(store, States::Locked)
+    .clock(clock)
         .state(States::Locked)
             .on(Event::OpenDoor)
                 .go_to(States::Unlocking)
@@ -55,13 +49,12 @@ fn create_lock() -> StateMachine<Event, States, Store> {
             .on(Event::Step)
                 .go_to(States::Unlocked)
                 .only_if(|store| store.lock_sensor == LockSensor::Unlocked)
-            .on(Event::Step)
+            .after(Duration::from_secs(10))
                 .go_to(States::Locked)
-                .only_if(|store| store.duration_in_state > Duration::from_secs(10))
         .state(States::Unlocked)
-            .on(Event::Step)
+            .after(Duration::from_secs(5))
                 .go_to(States::Locking)
-                .only_if(|store| store.door_sensor == DoorSensor::Closed && store.duration_in_state > Duration::from_secs(5))
+                .only_if(|store| store.door_sensor == DoorSensor::Closed)
         .state(States::Locking)
             .on(Event::Step)
                 .go_to(States::Locked)
@@ -69,7 +62,51 @@ fn create_lock() -> StateMachine<Event, States, Store> {
         .build()
 }
 
+fn create_self_stepping_lock() -> StateMachine<Event, States, Store> {
+    let store = Store {
+        lock_sensor: LockSensor::Unlocked,
+        door_sensor: DoorSensor::Closed,
+    };
+
+    // Unlocking checks the sensor on entry and moves on by itself, without a second trigger
+    StateMachineBuilder::new(store, States::Locked)
+        .state(States::Locked)
+            .on(Event::OpenDoor)
+                .go_to(States::Unlocking)
+        .state(States::Unlocking)
+            .on_enter_with_context(|context| {
+                if context.store.lock_sensor == LockSensor::Unlocked {
+                    context.raise(Event::Step);
+                }
+            })
+            .on(Event::Step)
+                .go_to(States::Unlocked)
+        .state(States::Unlocked)
+        .build()
+}
+
+fn create_deferring_lock() -> StateMachine<Event, States, Store> {
+    let store = Store {
+        lock_sensor: LockSensor::Unlocked,
+        door_sensor: DoorSensor::Closed,
+    };
 
+    // Opening the door while the bolt is moving is remembered instead of lost
+    StateMachineBuilder::new(store, States::Unlocked)
+        .state(States::Locked)
+            .on(Event::OpenDoor)
+                .go_to(States::Unlocking)
+        .state(States::Unlocking)
+        .state(States::Unlocked)
+            .on(Event::Step)
+                .go_to(States::Locking)
+        .state(States::Locking)
+            .defer(Event::OpenDoor)
+            .on(Event::Step)
+                .go_to(States::Locked)
+                .only_if(|store| store.lock_sensor == LockSensor::Locked)
+        .build()
+}
 
 #[cfg(test)]
 mod tests {
@@ -77,32 +114,28 @@ mod tests {
 
     #[test]
     fn test_open_door() {
-        let mut door_lock = create_lock();
+        let mut door_lock = create_timed_lock(ManualClock::new());
 
         door_lock.trigger(Event::OpenDoor);
         assert_eq!(door_lock.state, States::Unlocking);
 
+        door_lock.advance(Duration::from_secs(3));
         door_lock.trigger(Event::Step);
         assert_eq!(door_lock.state, States::Unlocking);
+        assert_eq!(door_lock.time_in_state(), Duration::from_secs(3));
 
         door_lock.store.lock_sensor = LockSensor::Unlocked;
         door_lock.trigger(Event::Step);
         assert_eq!(door_lock.state, States::Unlocked);
+        assert_eq!(door_lock.time_in_state(), Duration::ZERO);
 
-        door_lock.trigger(Event::Step);
-        assert_eq!(door_lock.state, States::Unlocked);
-
-        door_lock.store.door_sensor = DoorSensor::Open;
-        door_lock.trigger(Event::Step);
-        assert_eq!(door_lock.state, States::Unlocked);
-
-        door_lock.store.duration_in_state = Duration::from_secs(6);
-        door_lock.trigger(Event::Step);
+        door_lock.advance(Duration::from_secs(4));
         assert_eq!(door_lock.state, States::Unlocked);
 
-        door_lock.store.door_sensor = DoorSensor::Closed;
-        door_lock.trigger(Event::Step);
+        // Locking was entered at the 5 second deadline, a second ago
+        door_lock.advance(Duration::from_secs(2));
         assert_eq!(door_lock.state, States::Locking);
+        assert_eq!(door_lock.time_in_state(), Duration::from_secs(1));
 
         door_lock.trigger(Event::Step);
         assert_eq!(door_lock.state, States::Locking);
@@ -110,27 +143,200 @@ mod tests {
         door_lock.store.lock_sensor = LockSensor::Locked;
         door_lock.trigger(Event::Step);
         assert_eq!(door_lock.state, States::Locked);
+    }
+
+    #[test]
+    fn test_open_door_keeps_the_lock_unlocked() {
+        let mut door_lock = create_timed_lock(ManualClock::new());
+        door_lock.store.lock_sensor = LockSensor::Unlocked;
+        door_lock.trigger(Event::OpenDoor);
+        door_lock.trigger(Event::Step);
 
+        door_lock.store.door_sensor = DoorSensor::Open;
+        door_lock.advance(Duration::from_secs(6));
+        assert_eq!(door_lock.state, States::Unlocked);
+        assert_eq!(door_lock.time_in_state(), Duration::from_secs(6));
+
+        // The rejected timer is checked again once the door closes
+        door_lock.store.door_sensor = DoorSensor::Closed;
+        door_lock.advance(Duration::from_secs(1));
+        assert_eq!(door_lock.state, States::Locking);
+        assert_eq!(door_lock.time_in_state(), Duration::from_secs(1));
     }
 
     #[test]
     fn test_unlocking_to_locked_due_to_timeout() {
-        let mut door_lock = create_lock();
+        let mut door_lock = create_timed_lock(ManualClock::new());
 
         // Transition to Unlocking state
         door_lock.trigger(Event::OpenDoor);
         assert_eq!(door_lock.state, States::Unlocking);
 
-        // Simulate time passing without the lock sensor detecting an unlocked state
-        door_lock.store.duration_in_state = Duration::from_secs(11);
+        // Let time pass without the lock sensor detecting an unlocked state
+        door_lock.advance(Duration::from_secs(11));
 
-        // Trigger a step event
+        // Check that the state has transitioned to Locked due to timeout
+        assert_eq!(door_lock.state, States::Locked);
+        assert_eq!(door_lock.time_in_state(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_timed_unlocking_times_out() {
+        let clock = ManualClock::new();
+        let mut door_lock = create_timed_lock(clock.clone());
+
+        door_lock.trigger(Event::OpenDoor);
+        clock.advance(Duration::from_secs(9));
+        assert!(door_lock.poll().is_empty());
+        assert_eq!(door_lock.state, States::Unlocking);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(
+            door_lock.poll(),
+            vec![TriggerOutcome::Transitioned { from: States::Unlocking, to: States::Locked }]
+        );
+    }
+
+    #[test]
+    fn test_leaving_a_state_cancels_its_timers() {
+        let mut door_lock = create_timed_lock(ManualClock::new());
+
+        door_lock.trigger(Event::OpenDoor);
+        door_lock.advance(Duration::from_secs(4));
+
+        door_lock.store.lock_sensor = LockSensor::Unlocked;
         door_lock.trigger(Event::Step);
+        assert_eq!(door_lock.state, States::Unlocked);
 
-        // Check that the state has transitioned to Locked due to timeout
+        // Unlocking's 10 second timeout would be due now, but only Unlocked's timer fires
+        door_lock.advance(Duration::from_secs(7));
+        assert_eq!(door_lock.state, States::Locking);
+    }
+
+    #[test]
+    fn test_guarded_timed_transition() {
+        let create = |door_closed: bool| {
+            StateMachineBuilder::<Event, _, _, _>::new(door_closed, States::Unlocked)
+                .state(States::Unlocked)
+                    .after(Duration::from_secs(5)).go_to(States::Locking).only_if(|door_closed| *door_closed)
+                .state(States::Locking)
+                .build()
+        };
+
+        let mut open_door = create(false);
+        open_door.advance(Duration::from_secs(6));
+        assert_eq!(open_door.state, States::Unlocked);
+
+        let mut closed_door = create(true);
+        closed_door.advance(Duration::from_secs(6));
+        assert_eq!(closed_door.state, States::Locking);
+    }
+
+    #[test]
+    fn test_single_large_step_fires_chained_timers() {
+        let mut door_lock = StateMachineBuilder::<Event, _, _, _>::new((), States::Unlocked)
+            .state(States::Unlocked)
+                .after(Duration::from_secs(5)).go_to(States::Locking)
+            .state(States::Locking)
+                .after(Duration::from_secs(5)).go_to(States::Locked)
+            .state(States::Locked)
+            .build();
+
+        let outcomes = door_lock.advance(Duration::from_secs(12));
+        assert_eq!(outcomes.len(), 2);
         assert_eq!(door_lock.state, States::Locked);
     }
 
+    #[test]
+    fn test_raised_event_runs_to_completion() {
+        let mut door_lock = create_self_stepping_lock();
+
+        assert_eq!(
+            door_lock.trigger(Event::OpenDoor),
+            TriggerOutcome::Transitioned { from: States::Locked, to: States::Unlocking }
+        );
+        assert_eq!(door_lock.state, States::Unlocked);
+    }
+
+    #[test]
+    fn test_raising_events_in_a_loop_hits_the_step_limit() {
+        let mut machine = StateMachineBuilder::new(0, States::Locked)
+            .state(States::Locked)
+                .on(Event::Step).then_with_context(|context| {
+                    *context.store += 1;
+                    context.raise(Event::Step);
+                })
+            .build();
+
+        assert_eq!(
+            machine.trigger(Event::Step),
+            TriggerOutcome::StepLimitExceeded { state: States::Locked, dropped: 1 }
+        );
+        assert_eq!(machine.store, DEFAULT_MAX_STEPS + 1);
+
+        let mut limited = StateMachineBuilder::new(0, States::Locked)
+            .max_steps(3)
+            .state(States::Locked)
+                .on(Event::Step).then_with_context(|context| {
+                    context.raise(Event::Step);
+                    context.raise(Event::OpenDoor);
+                })
+            .build();
+
+        assert!(matches!(
+            limited.trigger(Event::Step),
+            TriggerOutcome::StepLimitExceeded { dropped: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_deferred_event_is_replayed_after_leaving_the_state() {
+        let mut door_lock = create_deferring_lock();
+        door_lock.trigger(Event::Step);
+
+        assert_eq!(door_lock.trigger(Event::OpenDoor), TriggerOutcome::Deferred { state: States::Locking });
+        assert_eq!(door_lock.deferred_events(), [Event::OpenDoor]);
+
+        // Still locking, so the door stays deferred
+        door_lock.trigger(Event::Step);
+        assert_eq!(door_lock.deferred_events(), [Event::OpenDoor]);
+
+        door_lock.store.lock_sensor = LockSensor::Locked;
+        door_lock.trigger(Event::Step);
+        assert_eq!(door_lock.state, States::Unlocking);
+        assert!(door_lock.deferred_events().is_empty());
+    }
+
+    #[test]
+    fn test_step_limit_keeps_deferred_events() {
+        let mut door_lock = StateMachineBuilder::new((), States::Locking)
+            .max_steps(0)
+            .state(States::Locking)
+                .defer(Event::OpenDoor)
+                .on(Event::Step).go_to(States::Locked)
+            .state(States::Locked)
+                .on(Event::OpenDoor).go_to(States::Unlocking)
+            .state(States::Unlocking)
+            .build();
+        door_lock.trigger(Event::OpenDoor);
+
+        assert_eq!(
+            door_lock.trigger(Event::Step),
+            TriggerOutcome::StepLimitExceeded { state: States::Locked, dropped: 0 }
+        );
+        assert_eq!(door_lock.deferred_events(), [Event::OpenDoor]);
+    }
+
+    #[test]
+    fn test_clear_deferred_events() {
+        let mut door_lock = create_deferring_lock();
+        door_lock.trigger(Event::Step);
+        door_lock.trigger(Event::OpenDoor);
 
+        assert_eq!(door_lock.clear_deferred(), vec![Event::OpenDoor]);
 
+        door_lock.store.lock_sensor = LockSensor::Locked;
+        door_lock.trigger(Event::Step);
+        assert_eq!(door_lock.state, States::Locked);
+    }
 }
@@ -0,0 +1,93 @@
+use fluent_state_machine::fixed::{CapacityError, StateMachine, StateMachineBuilder};
+use fluent_state_machine::TriggerOutcome;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Locked,
+    Unlocked,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Coin,
+    Push,
+    Kick,
+}
+
+// Counts the coins inserted and the pushes that got through.
+fn create_turnstile() -> StateMachine<Event, State, (u32, u32), 4> {
+    use Event::{Coin, Push};
+    use State::{Locked, Unlocked};
+
+    StateMachineBuilder::new((0, 0), Locked)
+        .state(Locked)
+            .on(Coin).go_to(Unlocked).update(|(coins, _)| *coins += 1)
+            .on(Push)
+        .state(Unlocked)
+            .on(Coin).update(|(coins, _)| *coins += 1)
+            .on(Push).go_to(Locked).then(|(_, passed)| *passed += 1)
+        .build()
+        .unwrap()
+}
+
+fn create_door_lock() -> StateMachine<&'static str, &'static str, u8, 3> {
+    StateMachineBuilder::new(0, "Locked")
+        .state("Locked")
+            .on("Unlock").go_to("Unlocked").only_if(|attempts| *attempts < 3)
+            .on("WrongCode").update(|attempts| *attempts += 1)
+        .state("Unlocked")
+            .on("Lock").go_to("Locked").then(|attempts| *attempts = 0)
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Event::{Coin, Kick, Push};
+    use State::{Locked, Unlocked};
+
+    #[test]
+    fn test_turnstile() {
+        let mut turnstile = create_turnstile();
+
+        assert_eq!(turnstile.trigger(Push), TriggerOutcome::SelfTransitioned { state: Locked });
+        assert_eq!(turnstile.trigger(Coin), TriggerOutcome::Transitioned { from: Locked, to: Unlocked });
+        assert_eq!(turnstile.trigger(Coin), TriggerOutcome::SelfTransitioned { state: Unlocked });
+        assert_eq!(turnstile.trigger(Push), TriggerOutcome::Transitioned { from: Unlocked, to: Locked });
+        assert_eq!(turnstile.trigger(Kick), TriggerOutcome::NoTransition { state: Locked });
+        assert_eq!(turnstile.store, (2, 1));
+    }
+
+    #[test]
+    fn test_door_lock_guard() {
+        let mut door_lock = create_door_lock();
+
+        for _ in 0..3 {
+            door_lock.trigger("WrongCode");
+        }
+        assert_eq!(door_lock.trigger("Unlock"), TriggerOutcome::Rejected { state: "Locked", guards: 1 });
+        assert_eq!(door_lock.state, "Locked");
+    }
+
+    #[test]
+    fn test_transitions_in_declaration_order() {
+        let turnstile = create_turnstile();
+        let events: Vec<_> = turnstile.transitions().map(|transition| (transition.from, &transition.event)).collect();
+
+        assert_eq!(events, [(Locked, &Coin), (Locked, &Push), (Unlocked, &Coin), (Unlocked, &Push)]);
+    }
+
+    #[test]
+    fn test_capacity_exceeded() {
+        let result = StateMachineBuilder::<Event, State, (), 2, _>::new((), Locked)
+            .state(Locked)
+                .on(Coin).go_to(Unlocked)
+                .on(Push)
+            .state(Unlocked)
+                .on(Push).go_to(Locked)
+            .build();
+
+        assert_eq!(result.err(), Some(CapacityError { capacity: 2, required: 3 }));
+    }
+}
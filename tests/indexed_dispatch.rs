@@ -0,0 +1,195 @@
+use std::rc::Rc;
+use std::time::Duration;
+use fluent_state_machine::{EventKind, StateMachine, StateMachineBuilder, TriggerOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum State {
+    Connected,
+    Idle,
+    Sending,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Event {
+    Send(u32),
+    Ack,
+    Close,
+    Reset,
+}
+
+impl EventKind for Event {}
+
+// Counts the bytes in flight and the retries used.
+fn create_connection(indexed: bool) -> StateMachine<Event, State, (u32, u32)> {
+    use Event::{Ack, Close, Reset, Send};
+    use State::{Closed, Connected, Idle, Sending};
+
+    let builder = StateMachineBuilder::new((0, 0), Idle);
+    let builder = if indexed { builder.indexed() } else { builder };
+
+    builder
+        .state(Connected)
+            .on(Close).go_to(Closed)
+        .state(Idle)
+            .substate_of(Connected)
+            .on(Send(0)).update(|(_, retries)| *retries += 1)
+            .on_kind(Send(0)).go_to(Sending).only_if_with_event(|_, event| matches!(event, Send(size) if *size <= 100))
+                .update_with_event(|(in_flight, _), event| {
+                    if let Send(size) = event {
+                        *in_flight = *size;
+                    }
+                })
+            .on_kind(Send(0)).go_to(Closed)
+        .state(Sending)
+            .substate_of(Connected)
+            .on(Ack).go_to(Idle).then(|(in_flight, _)| *in_flight = 0)
+            .after(Duration::from_secs(5)).go_to(Closed)
+        .state(Closed)
+            .on(Reset).go_to(Idle)
+        .build()
+}
+
+fn create_door(indexed: bool) -> StateMachine<&'static str, &'static str, ()> {
+    let builder = StateMachineBuilder::new((), "Closed");
+    let builder = if indexed { builder.indexed() } else { builder };
+
+    builder
+        .state("Closed")
+            .on("Open").go_to("Opened")
+            .on("Lock").go_to("Locked")
+        .state("Opened")
+            .on("Close").go_to("Closed")
+        .state("Locked")
+            .on("Unlock").go_to("Closed")
+        .build()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Digit(u8),
+    Letter(char),
+    Enter,
+}
+
+// Digits and letters are both typed input.
+impl EventKind for Key {
+    fn same_kind(&self, other: &Self) -> bool {
+        matches!(self, Self::Enter) == matches!(other, Self::Enter)
+    }
+}
+
+fn create_prompt(indexed: bool) -> StateMachine<Key, &'static str, u32> {
+    let builder = StateMachineBuilder::new(0, "Empty");
+    let builder = if indexed { builder.indexed() } else { builder };
+
+    builder
+        .state("Empty")
+            .on_kind(Key::Digit(0)).go_to("Typing").update(|typed| *typed += 1)
+        .state("Typing")
+            .on(Key::Letter('q')).go_to("Empty").then(|typed| *typed = 0)
+            .on_kind(Key::Letter('a')).update(|typed| *typed += 1)
+            .on(Key::Enter).go_to("Empty")
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Event::{Ack, Close, Reset, Send};
+    use State::{Closed, Idle, Sending};
+
+    fn run(indexed: bool) -> Vec<(TriggerOutcome<State>, (u32, u32))> {
+        let mut connection = create_connection(indexed);
+        [Send(0), Send(50), Ack, Send(500), Reset, Ack, Send(10), Close]
+            .into_iter()
+            .map(|event| (connection.trigger(event), connection.store))
+            .collect()
+    }
+
+    #[test]
+    fn test_indexed_matches_scan() {
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn test_indexed_keeps_declaration_order() {
+        let mut connection = create_connection(true);
+
+        // The exact `.on(Send(0))` comes first and is internal.
+        assert_eq!(connection.trigger(Send(0)), TriggerOutcome::SelfTransitioned { state: Idle });
+        assert_eq!(connection.store, (0, 1));
+
+        // Guarded alternatives of the same variant are tried in order.
+        assert_eq!(connection.trigger(Send(500)), TriggerOutcome::Transitioned { from: Idle, to: Closed });
+        assert_eq!(connection.trigger(Reset), TriggerOutcome::Transitioned { from: Closed, to: Idle });
+        assert_eq!(connection.trigger(Send(50)), TriggerOutcome::Transitioned { from: Idle, to: Sending });
+        assert_eq!(connection.store, (50, 1));
+    }
+
+    #[test]
+    fn test_indexed_bubbles_to_parent() {
+        let mut connection = create_connection(true);
+        connection.trigger(Send(50));
+
+        assert!(connection.can_trigger(&Close));
+        assert_eq!(connection.trigger(Close), TriggerOutcome::Transitioned { from: Sending, to: Closed });
+        assert_eq!(connection.trigger(Ack), TriggerOutcome::NoTransition { state: Closed });
+    }
+
+    #[test]
+    fn test_indexed_timeout() {
+        let mut connection = create_connection(true);
+        connection.trigger(Send(50));
+
+        assert_eq!(connection.advance(Duration::from_secs(4)), vec![]);
+        assert_eq!(
+            connection.advance(Duration::from_secs(1)),
+            vec![TriggerOutcome::Transitioned { from: Sending, to: Closed }]
+        );
+    }
+
+    #[test]
+    fn test_indexed_string_events() {
+        for indexed in [true, false] {
+            let mut door = create_door(indexed);
+
+            assert_eq!(door.trigger("Unlock"), TriggerOutcome::NoTransition { state: "Closed" });
+            assert_eq!(door.trigger("Lock"), TriggerOutcome::Transitioned { from: "Closed", to: "Locked" });
+            assert_eq!(door.trigger("Unlock"), TriggerOutcome::Transitioned { from: "Locked", to: "Closed" });
+            assert_eq!(door.trigger("Open"), TriggerOutcome::Transitioned { from: "Closed", to: "Opened" });
+        }
+    }
+
+    #[test]
+    fn test_indexed_kinds_span_variants() {
+        for indexed in [true, false] {
+            let mut prompt = create_prompt(indexed);
+
+            assert_eq!(prompt.trigger(Key::Letter('x')), TriggerOutcome::Transitioned { from: "Empty", to: "Typing" });
+            assert_eq!(prompt.trigger(Key::Digit(7)), TriggerOutcome::SelfTransitioned { state: "Typing" });
+            assert_eq!(prompt.store, 2);
+
+            // The exact `.on(Letter('q'))` is declared before the kind and wins.
+            assert_eq!(prompt.trigger(Key::Letter('q')), TriggerOutcome::Transitioned { from: "Typing", to: "Empty" });
+            assert_eq!(prompt.store, 0);
+        }
+    }
+
+    #[test]
+    fn test_indexed_local_machine_accepts_non_send_events() {
+        let call: Rc<str> = Rc::from("Call");
+        let hang_up: Rc<str> = Rc::from("HangUp");
+
+        let mut machine = StateMachineBuilder::new((), "Waiting")
+            .indexed()
+            .state("Waiting")
+                .on(Rc::clone(&call)).go_to("Answering")
+            .state("Answering")
+                .on(Rc::clone(&hang_up)).go_to("Waiting")
+            .build();
+
+        assert_eq!(machine.trigger(hang_up), TriggerOutcome::NoTransition { state: "Waiting" });
+        assert_eq!(machine.trigger(call), TriggerOutcome::Transitioned { from: "Waiting", to: "Answering" });
+    }
+}
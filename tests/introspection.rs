@@ -0,0 +1,88 @@
+use std::time::Duration;
+use fluent_state_machine::{EventKind, StateMachine, StateMachineBuilder, TransitionTrigger};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Brewing,
+    Ready,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Brew,
+    AddWater(u32),
+    Take,
+}
+
+impl EventKind for Event {}
+
+fn create_coffee_machine() -> StateMachine<Event, State, u32> {
+    use Event::{AddWater, Brew, Take};
+    use State::{Brewing, Idle, Ready};
+
+    StateMachineBuilder::new(0, Idle)
+        .state(Idle)
+            .on(Brew).go_to(Brewing).only_if_named("has water", |water| *water > 0)
+            .on_kind(AddWater(0)).update_with_event(|water, event| {
+                if let AddWater(amount) = event {
+                    *water += amount;
+                }
+            })
+        .state(Brewing)
+            .on_enter(|water| *water -= 1)
+            .defer(Take)
+            .after(Duration::from_secs(30)).go_to(Ready)
+        .state(Ready)
+            .on(Take).go_to(Idle)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let machine = create_coffee_machine();
+        let transitions: Vec<_> = machine.transitions().collect();
+
+        assert_eq!(transitions.len(), 4);
+        assert_eq!(transitions[0].from, State::Idle);
+        assert_eq!(transitions[0].to, State::Brewing);
+        assert_eq!(transitions[0].trigger, TransitionTrigger::On(&Event::Brew));
+        assert!(transitions[0].guarded);
+        assert_eq!(transitions[0].guard_name, Some("has water"));
+        assert!(!transitions[0].has_action);
+
+        assert!(transitions[1].internal);
+        assert!(transitions[1].has_action);
+        assert_eq!(transitions[1].trigger, TransitionTrigger::OnKind(&Event::AddWater(0)));
+        assert_eq!(transitions[2].trigger, TransitionTrigger::After(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_states_and_events() {
+        let machine = create_coffee_machine();
+        let states: Vec<_> = machine.states().map(|view| view.state).collect();
+        assert_eq!(states, [State::Idle, State::Brewing, State::Ready]);
+
+        let brewing = machine.states().find(|view| view.state == State::Brewing).unwrap();
+        assert!(brewing.has_entry_action);
+        assert_eq!(brewing.deferred, [TransitionTrigger::On(&Event::Take)]);
+
+        assert_eq!(machine.events(), [&Event::Brew, &Event::AddWater(0), &Event::Take]);
+        assert_eq!(machine.initial_states(), [State::Idle]);
+    }
+
+    #[test]
+    fn test_transitions_from_and_predecessors_of() {
+        let machine = create_coffee_machine();
+
+        let targets: Vec<_> = machine.transitions_from(State::Idle).map(|transition| transition.to).collect();
+        assert_eq!(targets, [State::Brewing, State::Idle]);
+
+        assert_eq!(machine.predecessors_of(State::Idle), [State::Ready]);
+        assert_eq!(machine.predecessors_of(State::Brewing), [State::Idle]);
+    }
+}
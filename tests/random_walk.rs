@@ -0,0 +1,150 @@
+use fluent_state_machine::testing::{Failure, RandomWalk};
+use fluent_state_machine::{StateMachine, StateMachineBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Play,
+    Stop,
+    Pause,
+    Backward,
+    Forward,
+}
+
+const EVENTS: [Event; 5] = [Event::Play, Event::Stop, Event::Pause, Event::Backward, Event::Forward];
+
+fn create_cd_player() -> StateMachine<Event, State, i32> {
+    use Event::{Backward, Forward, Pause, Play, Stop};
+    use State::{Paused, Playing, Stopped};
+
+    StateMachineBuilder::new(0, Stopped)
+        .state(Stopped)
+            .on(Play).go_to(Playing).only_if(|track| *track > 0)
+            .on(Forward).update(|track| *track += 1)
+            .on(Backward).update(|track| *track -= 1)
+        .state(Playing)
+            .on(Stop).go_to(Stopped).then(|track| *track = 0)
+            .on(Pause).go_to(Paused)
+        .state(Paused)
+            .on(Play).go_to(Playing)
+            .on(Stop).go_to(Stopped).then(|track| *track = 0)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Event::{Forward, Pause, Play};
+    use State::{Paused, Stopped};
+
+    #[test]
+    fn test_invariants_hold() {
+        RandomWalk::new(create_cd_player, EVENTS)
+            .seed(42)
+            .invariant("a playing track is selected", |state, track| *state == Stopped || *track > 0)
+            .check();
+    }
+
+    #[test]
+    fn test_failure_is_shrunk() {
+        let failure = RandomWalk::new(create_cd_player, EVENTS)
+            .seed(7)
+            .walks(1_000)
+            .invariant("only the first track is paused", |state, track| *state != Paused || *track <= 1)
+            .run()
+            .unwrap_err();
+
+        assert_eq!(
+            failure,
+            Failure {
+                invariant: "only the first track is paused",
+                seed: 7,
+                events: vec![Forward, Forward, Play, Pause],
+                state: Paused,
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "// invariant \"only the first track is paused\" breaks in state State::Paused (seed 7)\n\
+             machine.trigger(Event::Forward);\n\
+             machine.trigger(Event::Forward);\n\
+             machine.trigger(Event::Play);\n\
+             machine.trigger(Event::Pause);\n"
+        );
+    }
+
+    #[test]
+    fn test_same_seed_same_failure() {
+        let walk = || {
+            RandomWalk::new(create_cd_player, EVENTS)
+                .seed(3)
+                .invariant("the track is never negative", |_, track| *track >= 0)
+                .run()
+        };
+
+        assert_eq!(walk().unwrap_err().events, [Event::Backward]);
+        assert_eq!(walk(), walk());
+    }
+
+    #[test]
+    fn test_every_walk_starts_from_a_fresh_machine() {
+        // The action counts in its own capture, which a copy of one machine would share.
+        let turnstile = || {
+            let mut inserted = 0;
+            StateMachineBuilder::new(0, "Locked")
+                .state("Locked")
+                    .on("Coin").go_to("Unlocked").update(move |coins| {
+                        inserted += 1;
+                        *coins = inserted;
+                    })
+                .state("Unlocked")
+                    .on("Push").go_to("Locked")
+                .build()
+        };
+
+        RandomWalk::new(turnstile, ["Coin", "Push"])
+            .walks(50)
+            .steps(6)
+            .invariant("at most one coin per step", |_, coins| *coins <= 6)
+            .check();
+    }
+
+    #[test]
+    #[should_panic(expected = "machine.trigger(Event::Backward);")]
+    fn test_check_panics_with_reproducer() {
+        RandomWalk::new(create_cd_player, EVENTS)
+            .invariant("the track is never negative", |_, track| *track >= 0)
+            .check();
+    }
+
+    #[test]
+    fn test_string_events_print_as_literals() {
+        let turnstile = || {
+            StateMachineBuilder::new(0, "Locked")
+                .state("Locked")
+                    .on("Coin").go_to("Unlocked").update(|coins| *coins += 1)
+                .state("Unlocked")
+                    .on("Push").go_to("Locked")
+                .build()
+        };
+
+        let failure = RandomWalk::new(turnstile, ["Coin", "Push"])
+            .invariant("at most one coin", |_, coins| *coins <= 1)
+            .run()
+            .unwrap_err();
+
+        assert_eq!(
+            failure.to_string(),
+            "// invariant \"at most one coin\" breaks in state \"Unlocked\" (seed 24301)\n\
+             machine.trigger(\"Coin\");\n\
+             machine.trigger(\"Push\");\n\
+             machine.trigger(\"Coin\");\n"
+        );
+    }
+}
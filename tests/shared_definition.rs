@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use fluent_state_machine::{MachineDefinition, StateMachine, StateMachineBuilder, ThreadSafe, TriggerOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Waiting,
+    Paid,
+    Vending,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Coin,
+    Select,
+    Done,
+}
+
+fn create_vending_machine() -> StateMachine<Event, State, u32, ThreadSafe> {
+    use Event::{Coin, Done, Select};
+    use State::{Paid, Vending, Waiting};
+
+    StateMachineBuilder::new_thread_safe(0, Waiting)
+        .state(Waiting)
+            .on(Coin).go_to(Paid)
+        .state(Paid)
+            .on(Select).go_to(Vending)
+        .state(Vending)
+            .on_enter(|sold| *sold += 1)
+            .on(Done).go_to(Waiting)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Event::{Coin, Done, Select};
+    use State::{Paid, Vending, Waiting};
+
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_definition_is_send_and_sync() {
+        assert_send_sync::<MachineDefinition<Event, State, u32, ThreadSafe>>();
+    }
+
+    #[test]
+    fn test_local_machine_accepts_mutable_and_non_send_captures() {
+        let mut coins = 0;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let entered = Rc::clone(&log);
+
+        let mut machine = StateMachineBuilder::new(0, Waiting)
+            .state(Waiting)
+                .on(Coin).go_to(Paid).then(move |sold| {
+                    coins += 1;
+                    *sold = coins;
+                })
+            .state(Paid)
+                .on_enter(move |_| entered.borrow_mut().push(Paid))
+            .build();
+
+        machine.trigger(Coin);
+        assert_eq!(machine.store, 1);
+        assert_eq!(*log.borrow(), [Paid]);
+    }
+
+    #[test]
+    fn test_instances_share_definition() {
+        let machine = create_vending_machine();
+        let definition = Arc::clone(machine.definition());
+
+        let mut first = StateMachine::from_definition(Arc::clone(&definition), 0);
+        let mut second = StateMachine::from_definition(Arc::clone(&definition), 10);
+
+        first.trigger(Coin);
+        assert_eq!(first.state, Paid);
+        assert_eq!(second.state, Waiting);
+
+        second.trigger(Coin);
+        second.trigger(Select);
+        assert_eq!(second.state, Vending);
+        assert_eq!(second.store, 11);
+        assert_eq!(first.store, 0);
+
+        assert!(Arc::ptr_eq(first.definition(), second.definition()));
+        assert_eq!(Arc::strong_count(&definition), 4);
+    }
+
+    #[test]
+    fn test_instance_triggers_against_definition() {
+        let definition = Arc::clone(create_vending_machine().definition());
+        let mut instances: Vec<_> = (0..100).map(|_| definition.instance(0)).collect();
+
+        for (index, instance) in instances.iter_mut().enumerate() {
+            instance.trigger(&definition, Coin);
+            if index % 2 == 0 {
+                assert_eq!(instance.trigger(&definition, Select), TriggerOutcome::Transitioned { from: Paid, to: Vending });
+            }
+        }
+
+        assert_eq!(instances.iter().filter(|instance| instance.state == Vending).count(), 50);
+        assert_eq!(instances.iter().map(|instance| instance.store).sum::<u32>(), 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "a MachineInstance must be run with the MachineDefinition that created it")]
+    fn test_instance_refuses_another_definition() {
+        let definition = Arc::clone(create_vending_machine().definition());
+        let other = Arc::clone(create_vending_machine().definition());
+
+        let mut instance = definition.instance(0);
+        instance.trigger(&other, Coin);
+    }
+
+    #[test]
+    fn test_thread_safe_machine_accepts_mutable_captures() {
+        let mut coins = 0;
+        let mut machine = StateMachineBuilder::new_thread_safe(0, Waiting)
+            .state(Waiting)
+                .on(Coin).go_to(Paid).then(move |sold| {
+                    coins += 1;
+                    *sold = coins;
+                })
+            .state(Paid)
+                .on(Done).go_to(Waiting)
+            .build();
+
+        machine.trigger(Coin);
+        machine.trigger(Done);
+        machine.trigger(Coin);
+        assert_eq!(machine.store, 2);
+    }
+
+    #[test]
+    fn test_instances_across_threads() {
+        let definition = Arc::clone(create_vending_machine().definition());
+
+        // Spawn every thread before joining any of them.
+        #[allow(clippy::needless_collect)]
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let definition = Arc::clone(&definition);
+                thread::spawn(move || {
+                    let mut instance = definition.instance(0);
+                    for _ in 0..25 {
+                        instance.trigger(&definition, Coin);
+                        instance.trigger(&definition, Select);
+                        instance.trigger(&definition, Done);
+                    }
+                    instance.store
+                })
+            })
+            .collect();
+
+        let sold: u32 = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+        assert_eq!(sold, 100);
+    }
+}
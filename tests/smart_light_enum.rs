@@ -1,7 +1,8 @@
-use fluent_state_machine::{StateMachine, StateMachineBuilder};
+use fluent_state_machine::{EventKind, StateMachine, StateMachineBuilder, TriggerOutcome};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum LightStates {
+    Operational,
     Off,
     On,
     Dimmed,
@@ -15,8 +16,11 @@ enum LightEvents {
     Dim,
     Break,
     Repair,
+    SetBrightness(u8),
 }
 
+impl EventKind for LightEvents {}
+
 #[derive(Debug, Default)]
 struct LightStore {
     brightness: u8,
@@ -37,7 +41,7 @@ fn create_smart_light() -> StateMachine<LightEvents, LightStates, LightStore> {
         Off
     )
     .set_global_action(|store, state, event| {
-        println!("Transitioned to {:?} via {:?}", state, event);
+        println!("Transitioned to {state:?} via {event:?}");
         if matches!(event, TurnOn | TurnOff) {
             store.power_cycles += 1;
         }
@@ -49,7 +53,6 @@ fn create_smart_light() -> StateMachine<LightEvents, LightStates, LightStore> {
             .only_if(|store| store.is_functional)
         .on(Break)
             .go_to(Broken)
-            .then(|store| store.is_functional = false)
     .state(On)
         .on(TurnOff)
             .go_to(Off)
@@ -59,7 +62,20 @@ fn create_smart_light() -> StateMachine<LightEvents, LightStates, LightStore> {
             .update(|store| store.brightness = 30)
         .on(Break)
             .go_to(Broken)
-            .then(|store| store.is_functional = false)
+        .on_kind(SetBrightness(0))
+            .go_to(Dimmed)
+            .only_if_with_event(|_, event| matches!(event, SetBrightness(level) if *level < 50))
+            .then_with_event(|store, event| {
+                if let SetBrightness(level) = event {
+                    store.brightness = *level;
+                }
+            })
+        .on_kind(SetBrightness(0))
+            .update_with_event(|store, event| {
+                if let SetBrightness(level) = event {
+                    store.brightness = *level;
+                }
+            })
     .state(Dimmed)
         .on(TurnOff)
             .go_to(Off)
@@ -69,8 +85,8 @@ fn create_smart_light() -> StateMachine<LightEvents, LightStates, LightStore> {
             .update(|store| store.brightness = 100)
         .on(Break)
             .go_to(Broken)
-            .then(|store| store.is_functional = false)
     .state(Broken)
+        .on_enter(|store| store.is_functional = false)
         .on(Repair)
             .go_to(Off)
             .then(|store| {
@@ -80,6 +96,76 @@ fn create_smart_light() -> StateMachine<LightEvents, LightStates, LightStore> {
     .build()
 }
 
+// The same light with Off, On and Dimmed nested in Operational, which handles Break for all of them.
+fn create_hierarchical_light() -> StateMachine<LightEvents, LightStates, LightStore> {
+    use LightEvents::*;
+    use LightStates::*;
+
+    StateMachineBuilder::new(
+        LightStore { 
+            brightness: 0, 
+            is_functional: true, 
+            power_cycles: 0 
+        }, 
+        Off
+    )
+    .set_global_action(|store, state, event| {
+        println!("Transitioned to {state:?} via {event:?}");
+        if matches!(event, TurnOn | TurnOff) {
+            store.power_cycles += 1;
+        }
+    })
+    .state(Operational)
+        .initial_substate(Off)
+        .on(Break)
+            .go_to(Broken)
+    .state(Off)
+        .substate_of(Operational)
+        .on(TurnOn)
+            .go_to(On)
+            .update(|store| store.brightness = 100)
+            .only_if(|store| store.is_functional)
+    .state(On)
+        .substate_of(Operational)
+        .on(TurnOff)
+            .go_to(Off)
+            .update(|store| store.brightness = 0)
+        .on(Dim)
+            .go_to(Dimmed)
+            .update(|store| store.brightness = 30)
+        .on_kind(SetBrightness(0))
+            .go_to(Dimmed)
+            .only_if_with_event(|_, event| matches!(event, SetBrightness(level) if *level < 50))
+            .then_with_event(|store, event| {
+                if let SetBrightness(level) = event {
+                    store.brightness = *level;
+                }
+            })
+        .on_kind(SetBrightness(0))
+            .update_with_event(|store, event| {
+                if let SetBrightness(level) = event {
+                    store.brightness = *level;
+                }
+            })
+    .state(Dimmed)
+        .substate_of(Operational)
+        .on(TurnOff)
+            .go_to(Off)
+            .update(|store| store.brightness = 0)
+        .on(TurnOn)
+            .go_to(On)
+            .update(|store| store.brightness = 100)
+    .state(Broken)
+        .on_enter(|store| store.is_functional = false)
+        .on(Repair)
+            .go_to(Operational)
+            .then(|store| {
+                store.is_functional = true;
+                store.brightness = 0;
+            })
+    .build()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +257,187 @@ mod tests {
         assert_eq!(sm.store.power_cycles, 1); // Should only increment once
     }
 
+    #[test]
+    fn test_trigger_outcomes() {
+        let mut sm = create_smart_light();
+
+        assert_eq!(
+            sm.trigger(LightEvents::Dim),
+            TriggerOutcome::NoTransition { state: LightStates::Off }
+        );
+
+        assert_eq!(
+            sm.trigger(LightEvents::TurnOn),
+            TriggerOutcome::Transitioned { from: LightStates::Off, to: LightStates::On }
+        );
+
+        sm.trigger(LightEvents::Break);
+        sm.trigger(LightEvents::Repair);
+        sm.store.is_functional = false;
+        assert_eq!(
+            sm.trigger(LightEvents::TurnOn),
+            TriggerOutcome::Rejected { state: LightStates::Off, guards: 1 }
+        );
+    }
+
+    #[test]
+    fn test_entry_and_exit_actions_order() {
+        let mut sm = StateMachineBuilder::new(Vec::new(), LightStates::Off)
+            .state(LightStates::Off)
+                .on_exit(|log| log.push("exit Off"))
+                .on(LightEvents::TurnOn)
+                    .go_to(LightStates::On)
+                    .update(|log| log.push("update"))
+                    .then(|log| log.push("then"))
+            .state(LightStates::On)
+                .on_enter(|log| log.push("enter On"))
+                .on_exit(|log| log.push("exit On"))
+                .on(LightEvents::Dim)
+                    .then(|log| log.push("internal"))
+                .on(LightEvents::TurnOn)
+                    .go_to(LightStates::On)
+                    .then(|log| log.push("external"))
+            .build();
+
+        sm.trigger(LightEvents::TurnOn);
+        assert_eq!(sm.store, ["update", "exit Off", "then", "enter On"]);
+
+        sm.store.clear();
+        sm.trigger(LightEvents::Dim);
+        assert_eq!(sm.store, ["internal"]);
+
+        sm.store.clear();
+        sm.trigger(LightEvents::TurnOn);
+        assert_eq!(sm.store, ["exit On", "external", "enter On"]);
+    }
+
+    #[test]
+    fn test_event_payloads() {
+        let mut sm = create_smart_light();
+
+        assert_eq!(
+            sm.trigger(LightEvents::SetBrightness(80)),
+            TriggerOutcome::NoTransition { state: LightStates::Off }
+        );
+
+        sm.trigger(LightEvents::TurnOn);
+        sm.trigger(LightEvents::SetBrightness(80));
+        assert_eq!(sm.state, LightStates::On);
+        assert_eq!(sm.store.brightness, 80);
+
+        sm.trigger(LightEvents::SetBrightness(20));
+        assert_eq!(sm.state, LightStates::Dimmed);
+        assert_eq!(sm.store.brightness, 20);
+    }
+
+    #[test]
+    fn test_custom_event_kind() {
+        #[derive(PartialEq)]
+        struct Command {
+            name: &'static str,
+            level: u8,
+        }
+
+        impl EventKind for Command {
+            fn same_kind(&self, other: &Self) -> bool {
+                self.name == other.name
+            }
+        }
+
+        let mut sm = StateMachineBuilder::new(0, LightStates::On)
+            .state(LightStates::On)
+                .on_kind(Command { name: "brightness", level: 0 })
+                    .update_with_event(|brightness, command| *brightness = command.level)
+            .build();
+
+        sm.trigger(Command { name: "brightness", level: 40 });
+        assert_eq!(sm.store, 40);
+
+        sm.trigger(Command { name: "volume", level: 90 });
+        assert_eq!(sm.store, 40);
+    }
+
+    #[test]
+    fn test_superstate_handles_unhandled_events() {
+        let mut sm = create_hierarchical_light();
+        assert!(sm.is_in(LightStates::Operational));
+
+        sm.trigger(LightEvents::TurnOn);
+        sm.trigger(LightEvents::Dim);
+        assert!(sm.is_in(LightStates::Dimmed));
+        assert!(sm.is_in(LightStates::Operational));
+
+        assert_eq!(
+            sm.trigger(LightEvents::Break),
+            TriggerOutcome::Transitioned { from: LightStates::Dimmed, to: LightStates::Broken }
+        );
+        assert!(!sm.is_in(LightStates::Operational));
+
+        // Entering the composite state continues into its initial substate
+        sm.trigger(LightEvents::Repair);
+        assert_eq!(sm.state, LightStates::Off);
+    }
+
+    #[test]
+    fn test_hierarchical_light_matches_flat_light() {
+        use LightEvents::*;
+
+        let mut flat = create_smart_light();
+        let mut hierarchical = create_hierarchical_light();
+        let events = || [TurnOn, Dim, Break, TurnOn, Repair, TurnOn, SetBrightness(20), Break, Repair];
+
+        for (flat_event, hierarchical_event) in events().into_iter().zip(events()) {
+            assert_eq!(flat.trigger(flat_event), hierarchical.trigger(hierarchical_event));
+            assert_eq!(flat.state, hierarchical.state);
+        }
+        assert_eq!(flat.store.brightness, hierarchical.store.brightness);
+        assert_eq!(flat.store.power_cycles, hierarchical.store.power_cycles);
+    }
+
+    #[test]
+    fn test_hierarchical_entry_and_exit_order() {
+        let mut sm = StateMachineBuilder::new(Vec::new(), LightStates::Operational)
+            .state(LightStates::Operational)
+                .on_enter(|log| log.push("enter Operational"))
+                .on_exit(|log| log.push("exit Operational"))
+                .on(LightEvents::Break)
+                    .go_to(LightStates::Broken)
+                    .then(|log| log.push("break"))
+                .on(LightEvents::TurnOff)
+                    .go_to(LightStates::Operational)
+            .state(LightStates::Off)
+                .substate_of(LightStates::Operational)
+                .on_exit(|log| log.push("exit Off"))
+                .on(LightEvents::TurnOn).go_to(LightStates::On)
+            .state(LightStates::On)
+                .substate_of(LightStates::Operational)
+                .on_enter(|log| log.push("enter On"))
+                .on_exit(|log| log.push("exit On"))
+            .state(LightStates::Broken)
+                .on_enter(|log| log.push("enter Broken"))
+                .on(LightEvents::Repair).go_to(LightStates::On)
+            .build();
+
+        assert_eq!(sm.state, LightStates::Off);
+
+        sm.trigger(LightEvents::TurnOn);
+        assert_eq!(sm.store, ["exit Off", "enter On"]);
+
+        sm.store.clear();
+        sm.trigger(LightEvents::TurnOff);
+        assert_eq!(sm.state, LightStates::Off);
+        assert_eq!(sm.store, ["exit On", "exit Operational", "enter Operational"]);
+
+        sm.store.clear();
+        sm.trigger(LightEvents::Break);
+        assert_eq!(sm.store, ["exit Off", "exit Operational", "break", "enter Broken"]);
+
+        sm.store.clear();
+        sm.trigger(LightEvents::Repair);
+        assert_eq!(sm.state, LightStates::On);
+        assert_eq!(sm.store, ["enter Operational", "enter On"]);
+    }
+
     #[test]
     fn test_builder_prevents_unsafe_usage() {
         // This test demonstrates that the type system now prevents
@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+use fluent_state_machine::{RestoreError, Snapshot, StateMachine, StateMachineBuilder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum State {
+    Idle,
+    Paid,
+    Maintenance,
+}
+
+#[derive(PartialEq)]
+enum Event {
+    Coin,
+    Vend,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Store {
+    coins: u32,
+    stock: u32,
+}
+
+fn create_vending_machine() -> StateMachine<Event, State, Store> {
+    StateMachineBuilder::new(Store { coins: 0, stock: 5 }, State::Idle)
+        .state(State::Idle)
+            .on(Event::Coin).go_to(State::Paid).update(|store| store.coins += 1)
+        .state(State::Paid)
+            .on(Event::Vend).go_to(State::Idle).then(|store| store.stock -= 1)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_survives_a_restart() {
+        let mut machine = create_vending_machine();
+        machine.trigger(Event::Coin);
+
+        let json = serde_json::to_string(&machine.snapshot()).unwrap();
+        assert_eq!(json, r#"{"state":"Paid","store":{"coins":1,"stock":5}}"#);
+
+        let mut restarted = create_vending_machine();
+        restarted.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restarted.state, State::Paid);
+        assert_eq!(restarted.store, Store { coins: 1, stock: 5 });
+
+        restarted.trigger(Event::Vend);
+        assert_eq!(restarted.store.stock, 4);
+    }
+
+    #[test]
+    fn test_restore_rejects_states_outside_the_definition() {
+        let mut machine = create_vending_machine();
+        let store = Store { coins: 0, stock: 0 };
+
+        let snapshot = Snapshot { state: State::Maintenance, regions: Vec::new(), store: store.clone() };
+        assert_eq!(machine.restore(snapshot), Err(RestoreError::UndeclaredState { state: State::Maintenance }));
+
+        let snapshot = Snapshot { state: State::Idle, regions: vec![State::Paid], store };
+        assert_eq!(machine.restore(snapshot), Err(RestoreError::RegionCount { expected: 1, found: 2 }));
+
+        assert_eq!(machine.store, Store { coins: 0, stock: 5 });
+    }
+}
@@ -29,7 +29,7 @@ fn create_soda_machine() -> StateMachine<Event, State, (i32, i32)> {
             .on(Coin)
                 .go_to(Select)
             .on(Refill)
-                .update(|(coke, sprite)| {println!("Pow"); *coke = 3; *sprite = 3;})
+                .update(move |(coke, sprite)| {println!("Pow"); *coke = max_coke; *sprite = max_sprite;})
         .state(Select)
             .on(Coke)
                 .go_to(Start)
@@ -80,4 +80,60 @@ mod tests {
         assert_eq!(soda_machine.state, State::Start);
         assert_eq!(soda_machine.store, (3, 3));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_capturing_closures() {
+        use std::sync::mpsc;
+
+        let (sender, receiver) = mpsc::channel();
+        let price = 2;
+
+        let mut soda_machine = StateMachineBuilder::new(0, State::Start)
+            .set_global_action(move |_, state, _| sender.send(*state).unwrap())
+            .state(State::Start)
+                .on(Event::Coin)
+                    .update(|coins| *coins += 1)
+                    .go_to(State::Select)
+                    .only_if(move |coins| *coins >= price)
+            .state(State::Select)
+                .on(Event::Coke)
+                    .go_to(State::Start)
+                    .then(move |coins| *coins -= price)
+            .build();
+
+        soda_machine.trigger(Event::Coin);
+        assert_eq!(soda_machine.state, State::Start);
+
+        soda_machine.trigger(Event::Coin);
+        soda_machine.trigger(Event::Coke);
+        assert_eq!(soda_machine.state, State::Start);
+        assert_eq!(soda_machine.store, 0);
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![State::Select, State::Start]);
+    }
+
+    #[test]
+    fn test_guard_first_update_only_runs_for_the_accepted_transition() {
+        // Coke is sold out, so Sprite is served instead
+        let create = |guard_first: bool| {
+            let builder = StateMachineBuilder::new((0, 3), State::Select)
+                .state(State::Select)
+                    .on(Event::Coke).go_to(State::Start).only_if(|(coke, _)| *coke > 0).update(|(coke, _)| *coke -= 1);
+            let builder = if guard_first { builder.guard_first() } else { builder };
+            builder
+                    .on(Event::Coke).go_to(State::Start).only_if(|(_, sprite)| *sprite > 0).update(|(_, sprite)| *sprite -= 1)
+                    .guard_first()
+                .state(State::Start)
+                .build()
+        };
+
+        let mut update_first = create(false);
+        update_first.trigger(Event::Coke);
+        assert_eq!(update_first.store, (-1, 2));
+
+        let mut guard_first = create(true);
+        guard_first.trigger(Event::Coke);
+        assert_eq!(guard_first.state, State::Start);
+        assert_eq!(guard_first.store, (0, 2));
+    }
+}
@@ -0,0 +1,85 @@
+use fluent_state_machine::fixed::{StaticStateMachine, TransitionDef};
+use fluent_state_machine::TriggerOutcome;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Play,
+    Pause,
+    Stop,
+    Forward,
+}
+
+use Event::{Forward, Pause, Play, Stop};
+use State::{Paused, Playing, Stopped};
+
+// The store is the selected track.
+static CD_PLAYER: &[TransitionDef<Event, State, u32>] = &[
+    TransitionDef::new(Stopped, Play).go_to(Playing).only_if(|track| *track > 0),
+    TransitionDef::new(Stopped, Forward).update(|track| *track += 1),
+    TransitionDef::new(Playing, Pause).go_to(Paused),
+    TransitionDef::new(Playing, Stop).go_to(Stopped).then(|track| *track = 0),
+    TransitionDef::new(Paused, Play).go_to(Playing),
+    TransitionDef::new(Paused, Stop).go_to(Stopped).then(|track| *track = 0),
+];
+
+fn create_cd_player() -> StaticStateMachine<'static, Event, State, u32> {
+    StaticStateMachine::new(CD_PLAYER, 0, Stopped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cd_player() {
+        let mut cd_player = create_cd_player();
+
+        assert_eq!(cd_player.trigger(Play), TriggerOutcome::Rejected { state: Stopped, guards: 1 });
+        assert_eq!(cd_player.trigger(Forward), TriggerOutcome::SelfTransitioned { state: Stopped });
+        assert_eq!(cd_player.trigger(Play), TriggerOutcome::Transitioned { from: Stopped, to: Playing });
+        assert_eq!(cd_player.trigger(Pause), TriggerOutcome::Transitioned { from: Playing, to: Paused });
+        assert_eq!(cd_player.trigger(Pause), TriggerOutcome::NoTransition { state: Paused });
+        assert_eq!(cd_player.store, 1);
+
+        cd_player.trigger(Stop);
+        assert_eq!(cd_player.state, Stopped);
+        assert_eq!(cd_player.store, 0);
+    }
+
+    #[test]
+    fn test_machines_share_the_table() {
+        let mut first = create_cd_player();
+        let second = create_cd_player();
+
+        first.trigger(Forward);
+        first.trigger(Play);
+
+        assert_eq!(first.state, Playing);
+        assert_eq!(second.state, Stopped);
+        assert_eq!(second.transitions().count(), CD_PLAYER.len());
+    }
+
+    #[test]
+    fn test_const_machine() {
+        const TURNSTILE: StaticStateMachine<&str, &str, ()> = StaticStateMachine::new(
+            &[
+                TransitionDef::new("Locked", "Coin").go_to("Unlocked"),
+                TransitionDef::new("Unlocked", "Push").go_to("Locked"),
+            ],
+            (),
+            "Locked",
+        );
+
+        let mut turnstile = TURNSTILE;
+        turnstile.trigger("Coin");
+        assert_eq!(turnstile.state, "Unlocked");
+        assert_eq!(TURNSTILE.state, "Locked");
+    }
+}